@@ -0,0 +1,171 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+
+    Left,
+    Down,
+    Up,
+    Right,
+
+    PageLeft,
+    PageDown,
+    PageUp,
+    PageRight,
+
+    EdgeLeft,
+    EdgeDown,
+    EdgeUp,
+    EdgeRight,
+
+    First,
+    Last,
+    Next,
+    Previous,
+
+    Mark,
+    Reload,
+    ReloadAll,
+    Remove,
+
+    ToggleFullscreen,
+    ToggleBar,
+    ToggleMode,
+    ToggleAnimation,
+    ToggleSlideshow,
+    Antialias,
+    CommandBar,
+    ToggleInfo,
+
+    FlipHorizontal,
+    FlipVertical,
+    RotateLeft,
+    RotateRight,
+
+    ZoomIn,
+    ZoomOut,
+    ZoomActual,
+    ZoomFit,
+    ZoomFitWidth,
+    ZoomFitHeight,
+    ZoomFill,
+    ZoomShrink,
+
+    ThumbnailLarger,
+    ThumbnailSmaller,
+
+    RestartAnimation,
+    FrameNext,
+    FramePrevious,
+    FrameFirst,
+    FrameLast,
+    SpeedUp,
+    SpeedDown,
+    SpeedReset,
+    ToggleReverse,
+    TogglePingPong,
+
+    ExportFrame,
+    ExportFrames,
+
+    SortNext,
+    SortReverse,
+    Shuffle
+}
+
+/// The names used to refer to actions from the config file and the command bar
+const ACTIONS: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+
+    ("left", Action::Left),
+    ("down", Action::Down),
+    ("up", Action::Up),
+    ("right", Action::Right),
+
+    ("page-left", Action::PageLeft),
+    ("page-down", Action::PageDown),
+    ("page-up", Action::PageUp),
+    ("page-right", Action::PageRight),
+
+    ("edge-left", Action::EdgeLeft),
+    ("edge-down", Action::EdgeDown),
+    ("edge-up", Action::EdgeUp),
+    ("edge-right", Action::EdgeRight),
+
+    ("first", Action::First),
+    ("last", Action::Last),
+    ("next", Action::Next),
+    ("previous", Action::Previous),
+
+    ("mark", Action::Mark),
+    ("reload", Action::Reload),
+    ("reload-all", Action::ReloadAll),
+    ("remove", Action::Remove),
+
+    ("toggle-fullscreen", Action::ToggleFullscreen),
+    ("toggle-bar", Action::ToggleBar),
+    ("toggle-mode", Action::ToggleMode),
+    ("toggle-animation", Action::ToggleAnimation),
+    ("toggle-slideshow", Action::ToggleSlideshow),
+    ("antialias", Action::Antialias),
+    ("command", Action::CommandBar),
+    ("toggle-info", Action::ToggleInfo),
+
+    ("flip-horizontal", Action::FlipHorizontal),
+    ("flip-vertical", Action::FlipVertical),
+    ("rotate-left", Action::RotateLeft),
+    ("rotate-right", Action::RotateRight),
+
+    ("zoom-in", Action::ZoomIn),
+    ("zoom-out", Action::ZoomOut),
+    ("zoom-actual", Action::ZoomActual),
+    ("zoom-fit", Action::ZoomFit),
+    ("zoom-fit-width", Action::ZoomFitWidth),
+    ("zoom-fit-height", Action::ZoomFitHeight),
+    ("zoom-fill", Action::ZoomFill),
+    ("zoom-shrink", Action::ZoomShrink),
+
+    ("thumbnail-larger", Action::ThumbnailLarger),
+    ("thumbnail-smaller", Action::ThumbnailSmaller),
+
+    ("restart-animation", Action::RestartAnimation),
+    ("frame-next", Action::FrameNext),
+    ("frame-previous", Action::FramePrevious),
+    ("frame-first", Action::FrameFirst),
+    ("frame-last", Action::FrameLast),
+    ("speed-up", Action::SpeedUp),
+    ("speed-down", Action::SpeedDown),
+    ("speed-reset", Action::SpeedReset),
+    ("toggle-reverse", Action::ToggleReverse),
+    ("toggle-ping-pong", Action::TogglePingPong),
+
+    ("export-frame", Action::ExportFrame),
+    ("export-frames", Action::ExportFrames),
+
+    ("sort-next", Action::SortNext),
+    ("sort-reverse", Action::SortReverse),
+    ("shuffle", Action::Shuffle)
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter()
+            .find(|(_, action)| action == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    pub fn all() -> impl Iterator<Item = Action> {
+        ACTIONS.iter().map(|(_, action)| *action)
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ACTIONS.iter()
+            .find(|(action, _)| *action == name)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| format!("unknown action `{name}`"))
+    }
+}
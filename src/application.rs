@@ -1,8 +1,12 @@
 use crate::commands;
+use crate::config;
 use crate::constants::*;
+use crate::filter;
+use crate::paintable;
+use crate::sort;
 use crate::thumbnails;
 use crate::window::Window;
-use crate::paintable::SmartPaintable;
+use crate::zoom::{self, ZoomMode};
 
 
 use clap;
@@ -10,98 +14,91 @@ use gtk::gdk_pixbuf::Pixbuf;
 use gtk::{gdk, glib};
 use gtk::prelude::*;
 
-use std::path::PathBuf;
+use std::{cell::RefCell, path::{Path, PathBuf}};
 
 
 pub struct Application {
     window: &'static Window,
+    /// The files to open, taken once the window is built
+    files: RefCell<Vec<PathBuf>>
 }
 
 impl Application {
     pub fn new() -> glib::ExitCode {
+        let options = commands::commands().get_matches();
+
+        let mut config = match config::load(options.get_one::<PathBuf>("config").map(|p| p.as_path())) {
+            Ok(config) => config,
+            Err(error) => {
+                eprintln!("ponyview: {error}");
+                std::process::exit(1)
+            }
+        };
+
+        config.options.merge(&options);
+        config::init(config);
+
+        // these don't need a display, so they are done before gtk starts, like exporting frames
+        match run_cache_commands(&options) {
+            Ok(false) => {}
+            Ok(true) => std::process::exit(0),
+            Err(error) => {
+                eprintln!("ponyview: {error}");
+                std::process::exit(1)
+            }
+        }
+
+        let window: &'static Window = Box::leak(Box::new(Window::new()));
+        let files = find_files(&options, window);
+
+        if let Some(directory) = options.get_one::<PathBuf>("export-frames") {
+            std::process::exit(export_frames(&files, directory));
+        }
+
         let app = gtk::Application::builder()
             .application_id(APP_ID)
             .build();
 
-        let application = Self {
-            window: Box::leak(Box::new(Window::new()))
-        };
+        let application = Self { window, files: RefCell::new(files) };
 
         app.connect_activate(move |app| application.on_activate(app));
         app.connect_startup(|_| load_css());
 
         let empty: Vec<String> = vec![];
-        app.run_with_args(&empty)
+        let exit_code = app.run_with_args(&empty);
+
+        window.print_failures();
+        thumbnails::enforce_cache_limit();
+        exit_code
     }
 
     fn on_activate(&self, application: &gtk::Application) {
-        let mut command = commands::commands();
-        let options = command.clone().get_matches();
-
-        let mut active: Vec<PathBuf> = Vec::new();
-        active.extend(options.get_many::<PathBuf>("files")
-            .unwrap_or_default()
-            .map(|f| f.to_path_buf()));
-
-        for file in active.pop() {
-            if file.is_dir() {
-                active.extend(file.read_dir().unwrap().map(|e| e.unwrap().path()));
-            } else {
-                active.push(file);
-            }
-        }
+        let config = config::get();
+        let files = self.files.take();
 
-        while active.len() > 0 {
-            for file in active.pop() {
-                if file.is_dir() {
-                    if options.get_flag("recursive") {
-                        active.extend(file.read_dir().unwrap().map(|e| e.unwrap().path()));
-                    }
-                } else {
-                    self.window.files.lock().unwrap().push(file.to_path_buf());
-                }
-            }
-        }
+        self.window.sort_key.set(config.options.sort);
+        self.window.is_sort_reversed.set(config.options.reverse);
 
-        if options.get_flag("stdin") {
-            if options.get_many::<PathBuf>("files").unwrap_or_default().len() == 0 {
-                command.print_help();
-                std::process::exit(1)
+        self.window.is_thumbnail_mode.set(config.options.thumbnail);
+        match config.options.zoom {
+            Some(zoom) => {
+                self.window.zoom_mode.set(ZoomMode::Manual);
+                self.window.zoom.set(zoom::clamp(zoom as f64 / 100.0));
             }
 
-            // TODO: parse files from stdin
+            None => self.window.zoom_mode.set(config.settings.zoom_mode)
         }
 
-        self.window.is_thumbnail_mode.set(options.get_flag("thumbnail"));
         self.window.construct();
 
-        self.window.bar.get().unwrap().set_visible(!options.get_flag("no-bar"));
+        self.window.bar.get().unwrap().set_visible(!config.options.no_bar);
 
-        if options.get_flag("fullscreen") && !self.window.is_fullscreen() {
+        if config.options.fullscreen && !self.window.is_fullscreen() {
             self.window.toggle_fullscreen();
         }
 
-        for file in self.window.files.lock().unwrap().iter() {
-            let paintable = thumbnails::from_cache(file);
-            let paintable = if !paintable.is_some() {
-                thumbnails::to_cache(file);
-                thumbnails::from_cache(file).unwrap()
-            } else { paintable.unwrap() };
-
-            let frame = gtk::Box::builder()
-                .halign(gtk::Align::Start)
-                .hexpand(true)
-                .width_request(paintable.intrinsic_width())
-                .height_request(paintable.intrinsic_height())
-                .build();
-
-            let thumbnail = gtk::Picture::builder()
-                .paintable(&paintable)
-                .build();
-
-            frame.append(&thumbnail);
-            self.window.thumbnail_grid.get().unwrap().append(&frame);
-            self.window.thumbnails.lock().unwrap().push(paintable);
+        for file in files {
+            self.window.append_file(file);
         }
 
         if self.window.files.lock().unwrap().len() > 0 {
@@ -109,167 +106,19 @@ impl Application {
             self.window.set_highlighted_image(self.window.cursor.get());
         }
 
+        // some sort keys read every file, so the files are shown in the order they were found until then
+        self.window.sort_opened_files();
+
+        if let Some(seconds) = config.options.slideshow { self.window.start_slideshow(seconds) }
+
         let controller = gtk::EventControllerKey::new();
         let _connect_key_pressed = controller.connect_key_pressed(glib::clone!(@strong self.window as window => move |_, key, _state, modifier| {
-            match key {
-                gdk::Key::q => {
-                    window.quit(options.get_flag("stdout"));
-                }
-
-                gdk::Key::h => {
-                    if window.is_thumbnail_mode.get() {
-                        let cursor = window.cursor.get();
-                        if cursor > 1 { window.set_highlighted_image(cursor - 1) }
-                    } else { }
-
-                    window.cursor_changed();
-                }
-
-                gdk::Key::j => {
-                    if window.is_thumbnail_mode.get() {
-                        let binding = window.files.lock().unwrap();
-                        let length = binding.len();
-                        std::mem::drop(binding);
-
-                        let cursor = window.cursor.get();
-                        let stride = flow_box_get_stride(window.thumbnail_grid.get().unwrap(), length);
-
-                        if length >= cursor + stride { window.set_highlighted_image(cursor + stride) }
-                    } else { }
-
-                    window.cursor_changed();
-                }
-
-                gdk::Key::k => {
-                    if window.is_thumbnail_mode.get() {
-                        let guard = window.files.lock().unwrap();
-                        let length = guard.len();
-                        std::mem::drop(guard);
-
-                        let cursor = window.cursor.get();
-                        let stride = flow_box_get_stride(window.thumbnail_grid.get().unwrap(), length);
-
-                        if cursor > stride { window.set_highlighted_image(cursor - stride) }
-                    } else { }
-
-                    window.cursor_changed();
-                }
-
-                gdk::Key::l => {
-                    if window.is_thumbnail_mode.get() {
-                        let cursor = window.cursor.get();
-                        if window.files.lock().unwrap().len() > cursor {
-                            window.set_highlighted_image(cursor + 1);
-                        }
-                    } else { }
-
-                    window.cursor_changed();
-                }
-
-                gdk::Key::g => {
-                    window.cursor.set(1);
-                    window.cursor_changed();
-                }
-                gdk::Key::G => {
-                    let guard = window.files.lock().unwrap();
-                    let length = guard.len();
-                    std::mem::drop(guard);
-
-                    window.cursor.set(length);
-                    window.cursor_changed();
-                }
-
-                gdk::Key::n => {
-                    let cursor = window.cursor.get();
-                    if window.files.lock().unwrap().len() > cursor {
-                        window.cursor.set(cursor + 1);
-                        window.cursor_changed();
-                    }
-                }
-
-                gdk::Key::p => {
-                    let cursor = window.cursor.get();
-                    if cursor > 1 {
-                        window.cursor.set(cursor - 1);
-                        window.cursor_changed();
-                    }
-                }
-
-                gdk::Key::m => { window.mark_image(window.cursor.get()) }
-
-                gdk::Key::r => {
-                    window.image.get().unwrap().set_file(window.image.get().unwrap().file().as_ref());
-                }
-
-                gdk::Key::R => {
-                    // TODO: reload all thumbnails in thumbnail mode
-                }
-
-                gdk::Key::D => {
-                    if window.cursor.get() > 0 {
-                        let mut guard = window.files.lock().unwrap();
-                        guard.remove(0);
-                        std::mem::drop(guard);
-
-                        window.cursor.set(window.cursor.get() - 1);
-                        if window.cursor.get() > 0 { window.cursor_changed() }
-                    }
-                }
-
-                gdk::Key::f => { window.toggle_fullscreen() }
-                gdk::Key::b => {
-                    window.bar.get().unwrap().set_visible(!window.bar.get().unwrap().get_visible());
-                }
-
-                gdk::Key::a => {
-                    if !window.is_thumbnail_mode.get() {
-                        if modifier.contains(gdk::ModifierType::CONTROL_MASK) {
-                            let paintable = window.focused_image.borrow();
-                            if paintable.is_playing() { paintable.pause() } else { paintable.resume() }
-                        } else {
-                            let mut font_options = gtk::cairo::FontOptions::new().unwrap();
-                            font_options.set_antialias(gtk::cairo::Antialias::Best);
-                            window.image.get().unwrap().set_font_options(Some(&font_options));
-                        }
-                    }
-                }
-
-                gdk::Key::A => {
-                }
-
-                gdk::Key::vertbar => { window.flip_focused_image(true) }
-                gdk::Key::underscore => { window.flip_focused_image(false) }
-                gdk::Key::less => { window.rotate_focused_image(-90) }
-                gdk::Key::greater => { window.rotate_focused_image(90) }
-
-                gdk::Key::equal => {
-                }
-                gdk::Key::minus => {
-                }
-                gdk::Key::plus => {
-                }
-                gdk::Key::z => {
-                }
-
-                gdk::Key::colon => {
-                    // TODO cmdbar
-                }
-
-                gdk::Key::Return | gdk::Key::space => {
-                    if !modifier.contains(gdk::ModifierType::CONTROL_MASK) {
-                        window.is_thumbnail_mode.set(!window.is_thumbnail_mode.get());
-                        window.focused_view.get().unwrap().set_visible(!window.is_thumbnail_mode.get());
-                        window.thumbnail_view.get().unwrap().set_visible(window.is_thumbnail_mode.get());
-
-                        window.cursor_changed();
-                    } else if key == gdk::Key::space && !window.is_thumbnail_mode.get() {
-                        let paintable = window.focused_image.borrow();
-                        if paintable.is_playing() { paintable.pause() } else { paintable.resume() }
-                    }
-                }
-
-                _ => {}
-            };
+            if window.is_command_bar_open() { return glib::Propagation::Proceed }
+
+            let bindings = &config::get().bindings;
+            if let Some(action) = bindings.get(window.is_thumbnail_mode.get(), key, modifier) {
+                window.run_action(action);
+            }
 
             glib::Propagation::Proceed
         }));
@@ -281,24 +130,110 @@ impl Application {
     }
 }
 
-fn flow_box_get_stride(flow_box: &gtk::FlowBox, length: usize) -> usize {
-    if length < 1 { return 0 }
+/// The files named in `options` or on standard input, and the images found in the directories among them
+fn find_files(options: &clap::ArgMatches, window: &Window) -> Vec<PathBuf> {
+    let config = config::get();
 
-    let child = flow_box.child_at_index(0);
-    let y = child.unwrap().allocation().y();
+    let mut arguments: Vec<PathBuf> = Vec::new();
+    arguments.extend(options.get_many::<PathBuf>("files")
+        .unwrap_or_default()
+        .map(|f| f.to_path_buf()));
 
-    let mut index = 1;
-    for i in 1..length {
-        let child = flow_box.child_at_index(i as i32);
-        let cy = child.unwrap().allocation().y();
+    if options.get_flag("stdin") {
+        let separator = if options.get_flag("null") { b'\0' } else { b'\n' };
+        arguments.extend(read_stdin(separator));
+    }
+
+    let scan = filter::Scan::new(&config.options.include, &config.options.exclude);
+    let mut files: Vec<PathBuf> = Vec::new();
+    let read_dir = |directory: &PathBuf, active: &mut Vec<(PathBuf, bool)>| {
+        match directory.read_dir() {
+            Ok(entries) => {
+                let mut entries = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect::<Vec<PathBuf>>();
+                entries.sort_by(|a, b| sort::natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
 
-        index = i;
-        if y != cy { break }
+                // the stack takes them from the back, so they are pushed in reverse to come out in order
+                active.extend(entries.into_iter().rev().map(|e| (e, false)));
+            }
+            Err(error) => window.record_failure(directory, &error.to_string())
+        }
+    };
+
+    // directories that were named explicitly are always opened,
+    // the ones inside of them only when searching recursively.
+    // Files named explicitly are always opened too, the ones inside of directories only when they are images.
+    let mut active: Vec<(PathBuf, bool)> = arguments.into_iter().rev().map(|f| (f, true)).collect();
+    while let Some((file, is_argument)) = active.pop() {
+        if file.is_dir() {
+            if is_argument || config.options.recursive {
+                read_dir(&file, &mut active);
+            }
+        } else if is_argument || (file.is_file() && scan.keeps(&file)) {
+            files.push(file);
+        }
     }
 
-    index
+    files
 }
 
+/// Runs the thumbnail cache commands that were given, returns whether there were any
+fn run_cache_commands(options: &clap::ArgMatches) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut has_run = false;
+
+    if options.get_flag("clear-cache") {
+        thumbnails::clear_cache()?;
+        has_run = true;
+    }
+
+    if options.get_flag("prune-cache") {
+        let count = thumbnails::prune_cache()?;
+        println!("removed {count} thumbnail(s)");
+        has_run = true;
+    }
+
+    if options.get_flag("cache-stats") {
+        let (count, bytes) = thumbnails::cache_stats();
+        println!("{count} thumbnail(s), {bytes} bytes");
+        has_run = true;
+    }
+
+    Ok(has_run)
+}
+
+/// Exports the frames of every file, returns the exit code
+fn export_frames(files: &[PathBuf], directory: &Path) -> i32 {
+    let mut exit_code = 0;
+
+    for (file, stem) in files.iter().zip(paintable::export_stems(files)) {
+        match paintable::export_frames(file, directory, &stem) {
+            Ok(count) => println!("{}: exported {count} frame(s)", file.display()),
+            Err(error) => {
+                eprintln!("ponyview: {}: {error}", file.display());
+                exit_code = 1;
+            }
+        }
+    }
+
+    exit_code
+}
+
+/// Reads file names separated by `separator` from standard input
+fn read_stdin(separator: u8) -> Vec<PathBuf> {
+    use std::io::BufRead;
+    use std::os::unix::ffi::OsStringExt;
+
+    std::io::stdin()
+        .lock()
+        .split(separator)
+        .map_while(Result::ok)
+        .map(|mut name| {
+            if separator == b'\n' && name.last() == Some(&b'\r') { name.pop(); }
+            name
+        })
+        .filter(|name| !name.is_empty())
+        .map(|name| PathBuf::from(std::ffi::OsString::from_vec(name)))
+        .collect()
+}
 
 fn load_css() {
     // Load the CSS file and add it to the provider
@@ -0,0 +1,131 @@
+use crate::actions::Action;
+use crate::sort::SortKey;
+use crate::zoom::ZoomMode;
+
+
+const COMMANDS: &[(&str, &str)] = &[
+    ("goto", "goto <INDEX>"),
+    ("zoom", "zoom <PERCENT|MODE>"),
+    ("rotate", "rotate <DEGREES>"),
+    ("sort", "sort <KEY> [reverse]"),
+    ("filter", "filter [PATTERN]"),
+    ("help", "help")
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Action(Action),
+    Goto(usize),
+    Zoom(usize),
+    ZoomMode(ZoomMode),
+    Rotate(i32),
+    Sort(SortKey, bool),
+    Filter(Option<String>),
+    Help
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim().trim_start_matches(':');
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (line, None)
+        };
+
+        let command = match (name, argument) {
+            ("", _) => return Err("empty command".to_string()),
+
+            ("goto", Some(index)) => Command::Goto(parse_number(name, index)?),
+            ("zoom", Some(zoom)) => match zoom.parse::<ZoomMode>() {
+                Ok(mode) => Command::ZoomMode(mode),
+                Err(_) => Command::Zoom(parse_number(name, zoom.trim_end_matches('%'))?)
+            },
+            ("rotate", Some(angle)) => {
+                let angle = parse_number::<i32>(name, angle)?;
+                if angle % 90 != 0 { return Err(format!("{name}: angle must be a multiple of 90")) }
+                Command::Rotate(angle)
+            }
+
+            ("sort", Some(argument)) => {
+                let (key, is_reversed) = match argument.split_once(char::is_whitespace).map(|(key, rest)| (key, rest.trim())) {
+                    Some((key, "reverse")) => (key, true),
+                    Some(_) => return Err("usage: sort <KEY> [reverse]".to_string()),
+                    None => (argument, false)
+                };
+
+                Command::Sort(key.parse()?, is_reversed)
+            }
+            ("filter", pattern) => Command::Filter(pattern.map(str::to_string)),
+            ("help", None) => Command::Help,
+
+            (name, argument) => {
+                if let Some((_, usage)) = COMMANDS.iter().find(|(command, _)| *command == name) {
+                    return Err(format!("usage: {usage}"))
+                }
+
+                let action = name.parse::<Action>()?;
+                if argument.is_some() { return Err(format!("{name}: command takes no arguments")) }
+
+                Command::Action(action)
+            }
+        };
+
+        Ok(command)
+    }
+}
+
+pub fn help() -> String {
+    COMMANDS.iter()
+        .map(|(_, usage)| usage.to_string())
+        .chain(Action::all().map(|action| action.name().to_string()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, argument: &str) -> Result<T, String> {
+    argument.parse().map_err(|_| format!("{name}: invalid number `{argument}`"))
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+    position: Option<usize>
+}
+
+impl History {
+    pub fn push(&mut self, line: &str) {
+        self.position = None;
+        if line.trim().is_empty() { return }
+        if self.entries.last().is_some_and(|last| last == line) { return }
+
+        self.entries.push(line.to_string());
+    }
+
+    pub fn previous(&mut self) -> Option<&str> {
+        let position = match self.position {
+            Some(0) => 0,
+            Some(position) => position - 1,
+            None => self.entries.len().checked_sub(1)?
+        };
+
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position? + 1;
+        if position >= self.entries.len() {
+            self.position = None;
+            return Some("")
+        }
+
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+}
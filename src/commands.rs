@@ -1,3 +1,6 @@
+use crate::config;
+use crate::sort::SortKey;
+
 use clap::Command;
 
 pub fn commands() -> Command {
@@ -10,12 +13,36 @@ pub fn commands() -> Command {
                 .value_parser(clap::value_parser!(std::path::PathBuf)),
             clap::arg!(-b --"no-bar" "Start with the statusbar hidden"),
             clap::arg!(-f --fullscreen "Start in fullscreen mode"),
-            clap::arg!(-q --quiet "Quiet."),
+            clap::arg!(-q --quiet "Don't print a summary of files that failed to load"),
+            clap::arg!(--"drop-failed" "Remove files that failed to load from the list"),
             clap::arg!(-i --stdin "Read names of files to open from standard input"),
+            clap::arg!(-'0' --null "Names read from standard input are separated by NUL instead of newlines"),
             clap::arg!(-o --stdout "Write list of all marked files to standard output on quit"),
             clap::arg!(-r --recursive "Search for images in a directory recursively"), // SCARY
+            clap::arg!(--include <GLOB> "Only open files found in directories whose file name (or full path, if GLOB contains /) matches GLOB, can be repeated")
+                .required(false)
+                .action(clap::ArgAction::Append),
+            clap::arg!(--exclude <GLOB> "Skip files found in directories whose file name (or full path, if GLOB contains /) matches GLOB, can be repeated")
+                .required(false)
+                .action(clap::ArgAction::Append),
             clap::arg!(-t --thumbnail "Start in thumbnail mode"),
+            clap::arg!(--"no-auto-orient" "Ignore the EXIF orientation of images"),
+            clap::arg!(--"clear-cache" "Remove all cached thumbnails and exit"),
+            clap::arg!(--"prune-cache" "Remove cached thumbnails of files that no longer exist and exit"),
+            clap::arg!(--"cache-stats" "Print the number and size of cached thumbnails and exit"),
+            clap::arg!(--"export-frames" <DIRECTORY> "Write every frame of the files to DIRECTORY as numbered PNGs and exit")
+                .required(false)
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+            clap::arg!(--sort <KEY> "Sort the files by none, name, modified (or mtime), size, date, dimensions or shuffle")
+                .required(false)
+                .value_parser(|key: &str| key.parse::<SortKey>()),
+            clap::arg!(--reverse "Reverse the order of the files"),
             clap::arg!(-v --version "Print version information to standard output and exit"),
-            clap::arg!(-z --zoom "Set the zoom level percentage")])
+            clap::arg!(--slideshow <SECONDS> "Start a slideshow showing every image for SECONDS")
+                .required(false)
+                .value_parser(config::parse_seconds),
+            clap::arg!(-z --zoom <PERCENT> "Set the zoom level percentage")
+                .required(false)
+                .value_parser(clap::value_parser!(u32).range(1..))])
         .disable_version_flag(true)
 }
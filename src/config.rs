@@ -0,0 +1,384 @@
+use crate::actions::Action;
+use crate::constants::*;
+use crate::sort::SortKey;
+use crate::thumbnails::{ThumbnailAnimation, ThumbnailFormat};
+use crate::zoom::ZoomMode;
+
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::OnceLock, time::Duration};
+
+use gtk::gdk;
+use serde::Deserialize;
+
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("q", "quit"),
+
+    ("h", "left"),
+    ("j", "down"),
+    ("k", "up"),
+    ("l", "right"),
+    ("Left", "left"),
+    ("Down", "down"),
+    ("Up", "up"),
+    ("Right", "right"),
+
+    ("H", "page-left"),
+    ("J", "page-down"),
+    ("K", "page-up"),
+    ("L", "page-right"),
+
+    ("<Ctrl>h", "edge-left"),
+    ("<Ctrl>j", "edge-down"),
+    ("<Ctrl>k", "edge-up"),
+    ("<Ctrl>l", "edge-right"),
+
+    ("g", "first"),
+    ("G", "last"),
+    ("n", "next"),
+    ("p", "previous"),
+
+    ("m", "mark"),
+    ("r", "reload"),
+    ("R", "reload-all"),
+    ("D", "remove"),
+
+    ("f", "toggle-fullscreen"),
+    ("b", "toggle-bar"),
+    ("Return", "toggle-mode"),
+    ("space", "toggle-mode"),
+    ("<Ctrl>a", "toggle-animation"),
+    ("<Ctrl>space", "toggle-animation"),
+    ("a", "antialias"),
+    ("colon", "command"),
+    ("i", "toggle-info"),
+
+    ("bar", "flip-horizontal"),
+    ("underscore", "flip-vertical"),
+    ("less", "rotate-left"),
+    ("greater", "rotate-right"),
+
+    ("equal", "zoom-actual"),
+    ("minus", "zoom-out"),
+    ("plus", "zoom-in"),
+    ("z", "zoom-fit"),
+
+    ("BackSpace", "restart-animation"),
+    ("period", "frame-next"),
+    ("comma", "frame-previous"),
+    ("<Ctrl>comma", "frame-first"),
+    ("<Ctrl>period", "frame-last"),
+    ("bracketright", "speed-up"),
+    ("bracketleft", "speed-down"),
+    ("backslash", "speed-reset"),
+    ("<Ctrl>r", "toggle-reverse"),
+    ("<Ctrl>p", "toggle-ping-pong"),
+    ("e", "export-frame"),
+    ("E", "export-frames"),
+    ("s", "toggle-slideshow"),
+    ("o", "sort-next"),
+    ("O", "sort-reverse"),
+    ("S", "shuffle")
+];
+
+/// Bindings that only apply in thumbnail mode, on top of the default ones
+const DEFAULT_THUMBNAIL_BINDINGS: &[(&str, &str)] = &[
+    ("plus", "thumbnail-larger"),
+    ("minus", "thumbnail-smaller")
+];
+
+pub type Binding = (gdk::Key, gdk::ModifierType);
+
+#[derive(Default)]
+pub struct Config {
+    pub options: Options,
+    pub settings: Settings,
+    pub bindings: Bindings
+}
+
+/// Defaults for the command line flags, a flag given on the command line always wins
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Options {
+    pub no_bar: bool,
+    pub fullscreen: bool,
+    pub quiet: bool,
+    pub stdout: bool,
+    pub recursive: bool,
+    pub thumbnail: bool,
+    pub no_auto_orient: bool,
+    pub drop_failed: bool,
+    pub zoom: Option<u32>,
+    /// Start a slideshow showing every image for this many seconds
+    pub slideshow: Option<f64>,
+    pub sort: SortKey,
+    pub reverse: bool,
+    /// Only open the images in directories that match one of these patterns
+    pub include: Vec<String>,
+    /// Never open the files in directories that match one of these patterns
+    pub exclude: Vec<String>
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// The thumbnail size to start with
+    pub thumbnail_size: u32,
+    /// The sizes `thumbnail-larger` and `thumbnail-smaller` go through
+    pub thumbnail_sizes: Vec<u32>,
+    /// Threads generating thumbnails in the background, 0 uses one for every cpu
+    pub thumbnail_threads: usize,
+    /// Also compare the contents of a file to find outdated thumbnails, not only its modification time and size
+    pub thumbnail_content_hash: bool,
+    /// Share thumbnails with other applications through `~/.cache/thumbnails`
+    pub freedesktop_thumbnails: bool,
+    /// Megabytes the thumbnail cache may take up, the least recently used ones are removed first, 0 means no limit
+    pub thumbnail_cache_size: u64,
+    /// The format cached thumbnails are stored in
+    pub thumbnail_format: ThumbnailFormat,
+    /// Quality of JPEG and AVIF thumbnails, from 1 to 100
+    pub thumbnail_quality: u8,
+    /// How fast thumbnails are encoded, from 1 for the smallest files to 10 for the fastest
+    pub thumbnail_speed: u8,
+    /// Which animated thumbnails are played: all, highlighted or none
+    pub animate_thumbnails: ThumbnailAnimation,
+    /// Megabytes of decoded images that are kept around to switch between them quickly
+    pub image_cache_size: u64,
+    /// Images before and after the focused one that are decoded in the background
+    pub prefetch_count: usize,
+    /// Frames of an animation decoded ahead of playback, 0 decodes all of them up front.
+    /// As many are kept behind it for playing backward.
+    pub animation_buffer: usize,
+    /// Where `export-frame` and `export-frames` write the frames to
+    pub export_directory: PathBuf,
+    /// Seconds the slideshow shows every image for
+    pub slideshow_interval: f64,
+    /// Start over at the first image once the slideshow reaches the end
+    pub slideshow_loop: bool,
+    /// Show the images in a random order
+    pub slideshow_shuffle: bool,
+    /// Keep showing an animation until it played through once
+    pub slideshow_wait_for_animation: bool,
+    /// How images are scaled when no zoom level was given
+    pub zoom_mode: ZoomMode
+}
+
+pub struct Bindings {
+    pub image: HashMap<Binding, Action>,
+    pub thumbnail: HashMap<Binding, Action>
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    options: Options,
+    settings: Settings,
+    bindings: BindingsFile
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BindingsFile {
+    image: HashMap<String, String>,
+    thumbnail: HashMap<String, String>
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: THUMBNAIL_CHILD_SIZE as u32,
+            thumbnail_sizes: vec![64, 128, 256, 512],
+            thumbnail_threads: 0,
+            thumbnail_content_hash: false,
+            freedesktop_thumbnails: false,
+            thumbnail_cache_size: 512,
+            thumbnail_format: ThumbnailFormat::default(),
+            thumbnail_quality: 80,
+            thumbnail_speed: 4,
+            animate_thumbnails: ThumbnailAnimation::default(),
+            image_cache_size: 256,
+            prefetch_count: 2,
+            animation_buffer: 16,
+            export_directory: PathBuf::from("."),
+            slideshow_interval: 5.0,
+            slideshow_loop: true,
+            slideshow_shuffle: false,
+            slideshow_wait_for_animation: true,
+            zoom_mode: ZoomMode::default()
+        }
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        for (binding, action) in DEFAULT_BINDINGS {
+            bindings.insert(parse_binding(binding).unwrap(), action.parse().unwrap());
+        }
+
+        let mut thumbnail = bindings.clone();
+        for (binding, action) in DEFAULT_THUMBNAIL_BINDINGS {
+            thumbnail.insert(parse_binding(binding).unwrap(), action.parse().unwrap());
+        }
+
+        Self {
+            image: bindings,
+            thumbnail
+        }
+    }
+}
+
+impl Options {
+    pub fn merge(&mut self, matches: &clap::ArgMatches) {
+        self.no_bar |= matches.get_flag("no-bar");
+        self.fullscreen |= matches.get_flag("fullscreen");
+        self.quiet |= matches.get_flag("quiet");
+        self.stdout |= matches.get_flag("stdout");
+        self.recursive |= matches.get_flag("recursive");
+        self.thumbnail |= matches.get_flag("thumbnail");
+        self.no_auto_orient |= matches.get_flag("no-auto-orient");
+        self.drop_failed |= matches.get_flag("drop-failed");
+        self.reverse |= matches.get_flag("reverse");
+
+        if let Some(zoom) = matches.get_one::<u32>("zoom") { self.zoom = Some(*zoom) }
+        if let Some(seconds) = matches.get_one::<f64>("slideshow") { self.slideshow = Some(*seconds) }
+        if let Some(key) = matches.get_one::<SortKey>("sort") { self.sort = *key }
+        if let Some(patterns) = matches.get_many::<String>("include") { self.include.extend(patterns.cloned()) }
+        if let Some(patterns) = matches.get_many::<String>("exclude") { self.exclude.extend(patterns.cloned()) }
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, is_thumbnail_mode: bool, key: gdk::Key, modifier: gdk::ModifierType) -> Option<Action> {
+        let bindings = if is_thumbnail_mode { &self.thumbnail } else { &self.image };
+        let modifier = modifier & (gdk::ModifierType::CONTROL_MASK
+            | gdk::ModifierType::ALT_MASK
+            | gdk::ModifierType::SUPER_MASK
+            | gdk::ModifierType::SHIFT_MASK);
+
+        // shift is usually already part of the key itself (`G`, `plus`, ...)
+        bindings.get(&(key, modifier))
+            .or_else(|| bindings.get(&(key, modifier.difference(gdk::ModifierType::SHIFT_MASK))))
+            .copied()
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    let path = 'path: {
+        let path = std::env::var("XDG_CONFIG_HOME");
+        if path.is_ok() { break 'path PathBuf::from(&path.unwrap()) }
+
+        let path = std::env::home_dir().unwrap().join(".config");
+        path
+    };
+
+    path.join("ponyview").join("config.toml")
+}
+
+/// Loads the config from `path`, or from the default path if it exists
+pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn std::error::Error>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let path = default_path();
+            if !path.exists() { return Ok(Config::default()) }
+            path
+        }
+    };
+
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let file: ConfigFile = toml::from_str(&contents)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+
+    check_seconds(file.settings.slideshow_interval)
+        .map_err(|e| format!("{}: slideshow-interval: {e}", path.display()))?;
+    if let Some(seconds) = file.options.slideshow {
+        check_seconds(seconds).map_err(|e| format!("{}: slideshow: {e}", path.display()))?;
+    }
+
+    let mut bindings = Bindings::default();
+    extend_bindings(&mut bindings.image, &file.bindings.image, "image")
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    extend_bindings(&mut bindings.thumbnail, &file.bindings.thumbnail, "thumbnail")
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+
+    Ok(Config {
+        options: file.options,
+        settings: file.settings,
+        bindings
+    })
+}
+
+/// Parses the seconds of an interval, which have to be more than 0
+pub fn parse_seconds(value: &str) -> Result<f64, String> {
+    let seconds = value.parse::<f64>().map_err(|_| format!("invalid number `{value}`"))?;
+    check_seconds(seconds)
+}
+
+fn check_seconds(seconds: f64) -> Result<f64, String> {
+    if seconds > 0.0 && Duration::try_from_secs_f64(seconds).is_ok() {
+        Ok(seconds)
+    } else {
+        Err(format!("`{seconds}` is not a positive number of seconds"))
+    }
+}
+
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() { panic!("config was already initialized") }
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+fn extend_bindings(
+    bindings: &mut HashMap<Binding, Action>,
+    file: &HashMap<String, String>,
+    mode: &str
+) -> Result<(), String> {
+    for (key, action) in file {
+        let binding = parse_binding(key)
+            .ok_or_else(|| format!("[bindings.{mode}]: unknown key `{key}`"))?;
+
+        if action == "none" {
+            bindings.remove(&binding);
+            continue
+        }
+
+        let action = action.parse::<Action>()
+            .map_err(|e| format!("[bindings.{mode}]: {e} for key `{key}`"))?;
+        bindings.insert(binding, action);
+    }
+
+    Ok(())
+}
+
+/// Parses a key binding like `G`, `colon` or `<Ctrl>a`
+fn parse_binding(binding: &str) -> Option<Binding> {
+    let mut modifier = gdk::ModifierType::empty();
+    let mut key = binding;
+
+    while key.len() > 1 && key.starts_with('<') {
+        let (name, rest) = key[1..].split_once('>')?;
+        modifier |= match name.to_lowercase().as_str() {
+            "ctrl" | "control" => gdk::ModifierType::CONTROL_MASK,
+            "alt" => gdk::ModifierType::ALT_MASK,
+            "super" => gdk::ModifierType::SUPER_MASK,
+            "shift" => gdk::ModifierType::SHIFT_MASK,
+            _ => return None
+        };
+
+        key = rest;
+    }
+
+    let mut chars = key.chars();
+    let key = match (chars.next(), chars.next()) {
+        (Some(c), None) => gdk::Key::from_unicode(c),
+        (Some(_), Some(_)) => gdk::Key::from_name(key)?,
+        _ => return None
+    };
+
+    Some((key, modifier))
+}
@@ -3,3 +3,10 @@ pub const WINDOW_WIDTH: u16 = 640;
 pub const WINDOW_HEIGHT: u16 = 480;
 pub const THUMBNAIL_CHILD_SIZE: u8 = 32 * 4;
 pub const THUMBNAIL_CHILD_PADDING: u8 = 4;
+pub const ERROR_PLACEHOLDER_SIZE: i32 = 128;
+/// Threads decoding the images next to the focused one
+pub const PREFETCH_THREADS: usize = 2;
+/// Finished thumbnails that are shown per tick of the main loop
+pub const THUMBNAIL_BATCH_SIZE: usize = 32;
+/// The playback speeds animations step through
+pub const ANIMATION_SPEEDS: &[f64] = &[0.125, 0.25, 0.5, 1.0, 1.5, 2.0, 4.0, 8.0];
@@ -0,0 +1,124 @@
+use crate::format;
+
+use std::path::Path;
+
+
+/// A shell style pattern where `*` matches any run of characters and `?` a single one.
+/// Patterns with a `/` are matched against the whole path, the others against the file name.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pattern: Vec<char>,
+    is_path: bool
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        Self { pattern: pattern.chars().collect(), is_path: pattern.contains('/') }
+    }
+
+    pub fn has_wildcards(pattern: &str) -> bool {
+        pattern.contains(['*', '?'])
+    }
+
+    pub fn matches(&self, file: &Path) -> bool {
+        let text = if self.is_path { file.as_os_str() } else { file.file_name().unwrap_or_default() };
+        glob_match(&self.pattern, &text.to_string_lossy().chars().collect::<Vec<char>>())
+    }
+}
+
+/// Decides which of the files found in directories are opened
+pub struct Scan {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>
+}
+
+impl Scan {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        Self {
+            include: include.iter().map(|pattern| Pattern::new(pattern)).collect(),
+            exclude: exclude.iter().map(|pattern| Pattern::new(pattern)).collect()
+        }
+    }
+
+    /// Only images are kept, and of those only the ones matching an include pattern when there are any
+    pub fn keeps(&self, file: &Path) -> bool {
+        if self.exclude.iter().any(|pattern| pattern.matches(file)) { return false }
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.matches(file)) { return false }
+
+        format::is_supported(file)
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+
+    // where the last `*` was and how much of the text it took so far, to go back to when a match fails
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+
+                None => return false
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<char>>(), &text.chars().collect::<Vec<char>>())
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.png", "cat.png"));
+        assert!(!matches("*.png", "cat.jpg"));
+        assert!(matches("?at.png", "cat.png"));
+        assert!(!matches("?at.png", "at.png"));
+    }
+
+    #[test]
+    fn star_backtracks() {
+        assert!(matches("*ab", "aab"));
+        assert!(!matches("*ab", "aba"));
+        assert!(matches("a*b*c", "abbbc"));
+        assert!(matches("*a*b", "xaxxab"));
+    }
+
+    #[test]
+    fn trailing_stars_match_nothing() {
+        assert!(matches("cat*", "cat"));
+        assert!(matches("cat**", "cat"));
+        assert!(matches("*", ""));
+        assert!(!matches("a", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_match_the_path() {
+        assert!(Pattern::new("*.png").matches(Path::new("/photos/raw/cat.png")));
+        assert!(Pattern::new("*/raw/*").matches(Path::new("/photos/raw/cat.png")));
+        assert!(!Pattern::new("raw*").matches(Path::new("/photos/raw/cat.png")));
+    }
+}
@@ -0,0 +1,26 @@
+use std::{fs::File, io::Read, path::Path};
+
+use image::ImageFormat;
+
+
+/// Detects the format of an image from its magic bytes, the file
+/// extension is only used when the contents aren't recognized
+pub fn detect(bytes: &[u8], extension: &str) -> Option<ImageFormat> {
+    image::guess_format(bytes)
+        .ok()
+        .or_else(|| ImageFormat::from_extension(extension.to_ascii_lowercase()))
+}
+
+pub fn detect_file(bytes: &[u8], file: &Path) -> Option<ImageFormat> {
+    detect(bytes, file.extension().and_then(|e| e.to_str()).unwrap_or_default())
+}
+
+/// Whether `file` looks like an image we can decode, going by its extension or else its first bytes
+pub fn is_supported(file: &Path) -> bool {
+    let extension = file.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    if let Some(format) = ImageFormat::from_extension(extension.to_ascii_lowercase()) { return format.reading_enabled() }
+
+    let mut header = [0; 64];
+    let Ok(length) = File::open(file).and_then(|mut file| file.read(&mut header)) else { return false };
+    image::guess_format(&header[..length]).is_ok_and(|format| format.reading_enabled())
+}
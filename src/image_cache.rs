@@ -0,0 +1,61 @@
+use crate::paintable::SmartPaintable;
+
+use std::{collections::VecDeque, path::{Path, PathBuf}};
+
+
+/// Images that were decoded recently, the least recently used are dropped
+/// once they take up more than the budget
+pub struct ImageCache {
+    /// The most recently used entry is at the back, each one with its size in bytes
+    entries: VecDeque<(PathBuf, SmartPaintable, usize)>,
+    bytes: usize,
+    max_bytes: usize
+}
+
+impl ImageCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            bytes: 0,
+            max_bytes
+        }
+    }
+
+    pub fn contains(&self, file: &Path) -> bool {
+        self.entries.iter().any(|(cached, _, _)| cached == file)
+    }
+
+    pub fn get(&mut self, file: &Path) -> Option<SmartPaintable> {
+        let index = self.entries.iter().position(|(cached, _, _)| cached == file)?;
+        let entry = self.entries.remove(index)?;
+        let paintable = entry.1.clone();
+
+        self.entries.push_back(entry);
+        Some(paintable)
+    }
+
+    pub fn insert(&mut self, file: PathBuf, paintable: SmartPaintable) {
+        self.remove(&file);
+
+        let bytes = paintable.byte_size();
+        self.bytes += bytes;
+        self.entries.push_back((file, paintable, bytes));
+
+        // the newest entry stays, even when it is larger than the whole budget
+        while self.bytes > self.max_bytes && self.entries.len() > 1 {
+            let (_, _, bytes) = self.entries.pop_front().unwrap();
+            self.bytes -= bytes;
+        }
+    }
+
+    pub fn remove(&mut self, file: &Path) {
+        let Some(index) = self.entries.iter().position(|(cached, _, _)| cached == file) else { return };
+        let (_, _, bytes) = self.entries.remove(index).unwrap();
+        self.bytes -= bytes;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+}
@@ -1,9 +1,19 @@
 pub mod window;
+pub mod actions;
+pub mod cmdbar;
+pub mod config;
 pub mod commands;
 pub mod constants;
+pub mod filter;
+pub mod format;
+pub mod image_cache;
+pub mod metadata;
 pub mod paintable;
+pub mod pool;
+pub mod sort;
 pub mod thumbnails;
 pub mod application;
+pub mod zoom;
 
 
 use gtk::glib;
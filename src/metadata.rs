@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use image::{ImageDecoder, ImageReader};
+
+
+/// Collects everything we know about an image as a list of names and values
+pub fn read(file: &Path) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+
+    entries.push(("File".to_string(), file.display().to_string()));
+    if let Ok(metadata) = std::fs::metadata(file) {
+        entries.push(("File size".to_string(), format!("{} bytes", metadata.len())));
+    }
+
+    let bytes = match std::fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            entries.push(("Error".to_string(), error.to_string()));
+            return entries
+        }
+    };
+
+    read_image(&bytes, &mut entries);
+    read_exif(&bytes, &mut entries);
+    entries
+}
+
+/// When the photo was taken according to its EXIF data, formatted so that it sorts chronologically
+pub fn date_taken(file: &Path) -> Option<String> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(file).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+
+    [exif::Tag::DateTimeOriginal, exif::Tag::DateTime].iter()
+        .find_map(|tag| exif.get_field(*tag, exif::In::PRIMARY))
+        .map(|field| field.display_value().to_string())
+}
+
+fn read_image(bytes: &[u8], entries: &mut Vec<(String, String)>) {
+    let reader = match ImageReader::new(std::io::Cursor::new(bytes)).with_guessed_format() {
+        Ok(reader) => reader,
+        Err(_) => return
+    };
+
+    if let Some(format) = reader.format() {
+        entries.push(("Format".to_string(), format.to_mime_type().to_string()));
+    }
+
+    let mut decoder = match reader.into_decoder() {
+        Ok(decoder) => decoder,
+        Err(error) => {
+            entries.push(("Error".to_string(), error.to_string()));
+            return
+        }
+    };
+
+    let (width, height) = decoder.dimensions();
+    let color_type = decoder.color_type();
+
+    entries.push(("Dimensions".to_string(), format!("{width}x{height}")));
+    entries.push(("Color type".to_string(), format!("{color_type:?}")));
+    entries.push(("Bit depth".to_string(),
+        (color_type.bits_per_pixel() / color_type.channel_count() as u16).to_string()));
+    entries.push(("Alpha".to_string(), (if color_type.has_alpha() { "yes" } else { "no" }).to_string()));
+
+    if let Ok(Some(profile)) = decoder.icc_profile() {
+        let description = icc_description(&profile)
+            .unwrap_or_else(|| format!("embedded ({} bytes)", profile.len()));
+        entries.push(("Color profile".to_string(), description));
+    }
+}
+
+fn read_exif(bytes: &[u8], entries: &mut Vec<(String, String)>) {
+    let exif = match exif::Reader::new().read_from_container(&mut std::io::Cursor::new(bytes)) {
+        Ok(exif) => exif,
+        Err(_) => return
+    };
+
+    for field in exif.fields().filter(|field| field.ifd_num == exif::In::PRIMARY) {
+        entries.push((
+            field.tag.to_string(),
+            field.display_value().with_unit(&exif).to_string()));
+    }
+
+    let latitude = gps_coordinate(&exif, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S");
+    let longitude = gps_coordinate(&exif, exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W");
+    if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+        entries.push(("GPS position".to_string(), format!("{latitude:.6}, {longitude:.6}")));
+    }
+}
+
+/// Converts a degrees, minutes and seconds GPS field to decimal degrees
+fn gps_coordinate(exif: &exif::Exif, tag: exif::Tag, reference: exif::Tag, negative: &str) -> Option<f64> {
+    let field = exif.get_field(tag, exif::In::PRIMARY)?;
+    let values = match &field.value {
+        exif::Value::Rational(values) if values.len() == 3 => values,
+        _ => return None
+    };
+
+    let degrees = values[0].to_f64() + values[1].to_f64() / 60.0 + values[2].to_f64() / 3600.0;
+    let is_negative = exif.get_field(reference, exif::In::PRIMARY)
+        .is_some_and(|field| field.display_value().to_string().contains(negative));
+
+    Some(if is_negative { -degrees } else { degrees })
+}
+
+/// Reads the description tag of an ICC profile
+fn icc_description(profile: &[u8]) -> Option<String> {
+    let read_u32 = |offset: usize| -> Option<usize> {
+        let bytes = profile.get(offset..offset + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?) as usize)
+    };
+
+    let count = read_u32(128)?;
+    for i in 0..count {
+        let entry = 132 + i * 12;
+        if profile.get(entry..entry + 4)? != b"desc" { continue }
+
+        let (offset, size) = (read_u32(entry + 4)?, read_u32(entry + 8)?);
+        let tag = profile.get(offset..offset + size)?;
+
+        return match tag.get(0..4)? {
+            // ICC v2 stores a plain ascii string
+            b"desc" => {
+                let length = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+                let text = tag.get(12..12 + length)?;
+                Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string())
+            }
+
+            // ICC v4 stores localized UTF-16 strings, we use the first one
+            b"mluc" => {
+                let length = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+                let offset = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+                let text = tag.get(offset..offset + length)?
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect::<Vec<u16>>();
+
+                Some(String::from_utf16_lossy(&text))
+            }
+
+            _ => None
+        }
+    }
+
+    None
+}
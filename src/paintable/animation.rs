@@ -0,0 +1,61 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frames, ImageFormat, ImageResult
+};
+
+
+/// Creates a frame decoder for `bytes`, `None` if the image is not animated.
+///
+/// The image crate has no `AnimationDecoder` for AVIF, so those are always still images.
+pub fn frames<'a>(bytes: &'a [u8], format: ImageFormat) -> ImageResult<Option<Frames<'a>>> {
+    let read = Cursor::new(bytes);
+
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(read)?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(read)?;
+            if !decoder.is_apng()? { return Ok(None) }
+
+            decoder.apng()?.into_frames()
+        }
+
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(read)?;
+            if !decoder.has_animation() { return Ok(None) }
+
+            decoder.into_frames()
+        }
+
+        _ => return Ok(None)
+    };
+
+    Ok(Some(frames))
+}
+
+/// How many times an animation should be played, `None` means forever
+pub fn loop_count(bytes: &[u8], format: ImageFormat) -> Option<u32> {
+    let find = |tag: &[u8]| bytes.windows(tag.len()).position(|window| window == tag);
+    let read_u16 = |offset: usize| bytes.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+
+    let count = match format {
+        // the netscape extension counts repetitions after the first play,
+        // without it the animation is played once
+        ImageFormat::Gif => match find(b"NETSCAPE2.0") {
+            Some(offset) => read_u16(offset + 13).map(|count| if count == 0 { 0 } else { count + 1 }),
+            None => Some(1)
+        },
+
+        ImageFormat::Png => find(b"acTL")
+            .and_then(|offset| bytes.get(offset + 8..offset + 12))
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+
+        ImageFormat::WebP => find(b"ANIM").and_then(|offset| read_u16(offset + 12)),
+
+        _ => None
+    };
+
+    count.filter(|count| *count > 0)
+}
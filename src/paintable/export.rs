@@ -0,0 +1,59 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::Duration};
+
+use image::ImageFormat;
+
+use super::frame;
+
+
+/// Lists the exported frames with their durations in milliseconds, one per line
+const SIDECAR_EXTENSION: &str = "frames";
+
+/// Exports frames to `directory` as `<stem>-<index>.png`, counting from 1 like the bar does.
+/// Frames that were exported before stay in the sidecar file.
+pub struct Sidecar {
+    directory: PathBuf,
+    stem: String,
+    durations: BTreeMap<String, String>,
+    count: usize
+}
+
+impl Sidecar {
+    pub fn open(directory: &Path, stem: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(directory)?;
+
+        let durations = std::fs::read_to_string(directory.join(format!("{stem}.{SIDECAR_EXTENSION}")))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, duration)| (name.to_string(), duration.to_string()))
+            .collect();
+
+        Ok(Self { directory: directory.to_path_buf(), stem: stem.to_string(), durations, count: 0 })
+    }
+
+    /// Records frame `index` and returns where to save it
+    pub fn add(&mut self, index: usize, duration: Duration) -> PathBuf {
+        let name = format!("{}-{:04}.png", self.stem, index + 1);
+        let path = self.directory.join(&name);
+
+        self.durations.insert(name, duration.as_millis().to_string());
+        self.count += 1;
+        path
+    }
+
+    pub fn write_frame(&mut self, index: usize, frame: &image::Frame) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.add(index, frame::duration(frame));
+        frame.buffer().save_with_format(path, ImageFormat::Png)?;
+        Ok(())
+    }
+
+    /// Writes the sidecar file, returns how many frames were exported
+    pub fn finish(self) -> Result<usize, Box<dyn std::error::Error>> {
+        let lines = self.durations.iter()
+            .map(|(name, duration)| format!("{name}\t{duration}\n"))
+            .collect::<String>();
+        std::fs::write(self.directory.join(format!("{}.{SIDECAR_EXTENSION}", self.stem)), lines)?;
+
+        Ok(self.count)
+    }
+}
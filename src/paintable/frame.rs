@@ -2,19 +2,23 @@ use std::time::Duration;
 
 use gtk::{gdk, glib, prelude::*};
 
+#[derive(Clone)]
 pub struct Frame {
     pub texture: gdk::Texture,
     pub frame_duration: Duration,
 }
 
+/// How long `frame` is shown
+pub fn duration(frame: &image::Frame) -> Duration {
+    let frame_duration = Duration::from(frame.delay());
+
+    // convention is to use 100 milliseconds duration if it is defined as 0.
+    if frame_duration.is_zero() { Duration::from_millis(100) } else { frame_duration }
+}
+
 impl From<image::Frame> for Frame {
     fn from(f: image::Frame) -> Self {
-        let mut frame_duration = Duration::from(f.delay());
-
-        // convention is to use 100 milliseconds duration if it is defined as 0.
-        if frame_duration.is_zero() {
-            frame_duration = Duration::from_millis(100);
-        }
+        let frame_duration = duration(&f);
 
         let samples = f.into_buffer().into_flat_samples();
         let bytes = glib::Bytes::from(samples.as_slice());
@@ -2,17 +2,67 @@ use std::cell::{Cell, RefCell};
 
 use gtk::{gdk, glib, graphene, prelude::*, subclass::prelude::*};
 
-use super::Frame;
+use crate::constants::*;
+
+use super::{stream::FrameStream, Frame, Orientation};
 
-#[derive(Default)]
 pub struct SmartPaintable {
     pub frames: RefCell<Option<Vec<Frame>>>,
-    pub next_frame: RefCell<Option<gdk::Texture>>,
+    /// Decodes the frames of a large animation while it plays, instead of keeping all of them in `frames`
+    pub stream: RefCell<Option<FrameStream>>,
+    pub next_frame: RefCell<Option<Frame>>,
     pub timeout_source_id: RefCell<Option<glib::SourceId>>,
+    /// The frame that is shown
     pub current_idx: Cell<usize>,
+    /// A frame that is shown as soon as the stream has decoded it
+    pub pending_idx: Cell<Option<usize>>,
+    /// How often the animation is played, `None` loops forever
+    pub loop_count: Cell<Option<u32>>,
+    pub loops_played: Cell<u32>,
+    /// Frame durations are divided by this
+    pub speed: Cell<f64>,
+    pub is_reversed: Cell<bool>,
+    /// Whether the animation turns around at its ends instead of starting over
+    pub is_ping_pong: Cell<bool>,
+    /// The direction the animation plays in right now, ping-pong turns it around at the ends
+    pub is_backward: Cell<bool>,
+
+    pub orientation: Cell<Orientation>,
+    /// The orientation the image was loaded with, from its EXIF data
+    pub auto_orientation: Cell<Orientation>,
+    pub is_auto_oriented: Cell<bool>,
+    pub scale: Cell<f64>,
+    pub is_playing: Cell<bool>,
 
-    pub rotation: Cell<i32>,
-    pub is_playing: Cell<bool>
+    /// Why the image couldn't be loaded, a placeholder is drawn instead
+    pub error: RefCell<Option<String>>
+}
+
+impl Default for SmartPaintable {
+    fn default() -> Self {
+        Self {
+            frames: RefCell::new(None),
+            stream: RefCell::new(None),
+            next_frame: RefCell::new(None),
+            timeout_source_id: RefCell::new(None),
+            current_idx: Cell::new(0),
+            pending_idx: Cell::new(None),
+            loop_count: Cell::new(None),
+            loops_played: Cell::new(0),
+            speed: Cell::new(1.0),
+            is_reversed: Cell::new(false),
+            is_ping_pong: Cell::new(false),
+            is_backward: Cell::new(false),
+
+            orientation: Cell::new(Orientation::default()),
+            auto_orientation: Cell::new(Orientation::default()),
+            is_auto_oriented: Cell::new(false),
+            scale: Cell::new(1.0),
+            is_playing: Cell::new(false),
+
+            error: RefCell::new(None)
+        }
+    }
 }
 
 #[glib::object_subclass]
@@ -24,32 +74,77 @@ impl ObjectSubclass for SmartPaintable {
 
 impl ObjectImpl for SmartPaintable {}
 
-impl PaintableImpl for SmartPaintable {
-    fn intrinsic_height(&self) -> i32 {
-        self.next_frame
+impl SmartPaintable {
+    /// The size of the current frame with the orientation applied
+    pub fn image_size(&self) -> Option<(i32, i32)> {
+        let (width, height) = self.next_frame
             .borrow()
             .as_ref()
-            .map(|texture| texture.height())
+            .map(|frame| (frame.texture.width(), frame.texture.height()))?;
+
+        if self.orientation.get().is_transposed() { Some((height, width)) } else { Some((width, height)) }
+    }
+
+    fn display_size(&self) -> Option<(i32, i32)> {
+        if self.error.borrow().is_some() { return Some((ERROR_PLACEHOLDER_SIZE, ERROR_PLACEHOLDER_SIZE)) }
+        self.image_size()
+    }
+
+    fn snapshot_error(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let size = width.min(height) as f32 / 2.0;
+        let thickness = size / 8.0;
+        let color = gdk::RGBA::new(0.6, 0.1, 0.1, 1.0);
+
+        snapshot.append_color(
+            &gdk::RGBA::new(0.15, 0.15, 0.15, 1.0),
+            &graphene::Rect::new(0f32, 0f32, width as f32, height as f32)
+        );
+
+        snapshot.save();
+        snapshot.translate(&graphene::Point::new(cx, cy));
+        snapshot.rotate(45.0);
+        snapshot.append_color(&color, &graphene::Rect::new(-size / 2.0, -thickness / 2.0, size, thickness));
+        snapshot.append_color(&color, &graphene::Rect::new(-thickness / 2.0, -size / 2.0, thickness, size));
+        snapshot.restore();
+    }
+}
+
+impl PaintableImpl for SmartPaintable {
+    fn intrinsic_height(&self) -> i32 {
+        self.display_size()
+            .map(|(_, height)| (height as f64 * self.scale.get()).round() as i32)
             .unwrap_or(-1)
     }
 
     fn intrinsic_width(&self) -> i32 {
-        self.next_frame
-            .borrow()
-            .as_ref()
-            .map(|texture| texture.width())
+        self.display_size()
+            .map(|(width, _)| (width as f64 * self.scale.get()).round() as i32)
             .unwrap_or(-1)
     }
 
     fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
-        if let Some(texture) = &*self.next_frame.borrow() {
+        if self.error.borrow().is_some() {
+            self.snapshot_error(snapshot, width, height);
+        } else if let Some(Frame { texture, .. }) = &*self.next_frame.borrow() {
+            let orientation = self.orientation.get();
             let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
 
+            // the texture is drawn in its own coordinates and then turned into place
+            let (texture_width, texture_height) = if orientation.is_transposed() {
+                (height, width)
+            } else {
+                (width, height)
+            };
+
+            snapshot.save();
             snapshot.translate(&graphene::Point::new(cx, cy));
-            snapshot.rotate(self.rotation.get() as f32);
-            snapshot.translate(&graphene::Point::new(-cx, -cy));
+            snapshot.rotate(orientation.angle());
+            if orientation.is_mirrored { snapshot.scale(-1.0, 1.0) }
+            snapshot.translate(&graphene::Point::new(-texture_width as f32 / 2.0, -texture_height as f32 / 2.0));
 
-            texture.snapshot(snapshot, width, height);
+            texture.snapshot(snapshot, texture_width, texture_height);
+            snapshot.restore();
         } else {
             snapshot.append_color(
                 &gdk::RGBA::BLACK,
@@ -1,11 +1,26 @@
 mod imp;
+mod export;
 mod frame;
+mod orientation;
+mod stream;
+pub mod animation;
 
-use std::{io::Cursor, path::PathBuf};
+use std::{collections::HashSet, io::Cursor, path::{Path, PathBuf}, sync::Arc, time::Duration};
 
-use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*};
-use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageReader};
+use crate::config;
+use crate::format;
+
+use gtk::{gdk, glib, prelude::*, subclass::prelude::*};
+use image::ImageReader;
 use frame::Frame;
+use stream::{FrameStream, Next};
+pub use orientation::Orientation;
+
+
+/// How long to wait for a streamed frame the decoder hasn't finished yet
+const STREAM_RETRY: Duration = Duration::from_millis(10);
+/// Stands for the last frame before a stream knows how many frames there are
+const LAST_FRAME: usize = usize::MAX;
 
 
 glib::wrapper! {
@@ -18,89 +33,358 @@ impl Default for SmartPaintable {
     }
 }
 
+/// The frames of an image decoded into memory. Unlike textures these can be
+/// sent between threads, so the expensive decoding can happen off the main thread.
+pub struct Decoded {
+    frames: Vec<image::Frame>,
+    loop_count: Option<u32>,
+    orientation: Option<Orientation>,
+    /// Brings the frames after the first one when the animation is streamed
+    stream: Option<FrameStream>
+}
+
+impl Decoded {
+    /// The decoding consists of decoding the animation with the `AnimationDecoder`
+    /// of its format, a still image becomes a single frame.
+    pub fn from_bytes(bytes: &[u8], extension: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let format = format::detect(bytes, extension)
+            .ok_or("Failed to detect the image format")?;
+
+        let (frames, loop_count) = if let Some(frames) = animation::frames(bytes, format)? {
+            (frames.collect_frames()?, animation::loop_count(bytes, format))
+        } else {
+            let mut reader = ImageReader::new(Cursor::new(bytes));
+            reader.set_format(format);
+            (vec![image::Frame::new(reader.decode()?.into_rgba8())], None)
+        };
+
+        Ok(Self { frames, loop_count, orientation: orientation(bytes), stream: None })
+    }
+
+    pub fn from_file(file: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(file)?;
+        Self::from_bytes(&bytes, file.extension().unwrap_or_default().to_str().unwrap_or_default())
+    }
+
+    /// Like `from_file`, but only the first frame of an animation is decoded right away,
+    /// the others are decoded on another thread while it plays
+    pub fn from_file_streamed(file: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(file)?;
+        let extension = file.extension().unwrap_or_default().to_str().unwrap_or_default();
+
+        let capacity = config::get().settings.animation_buffer;
+        let format = format::detect(&bytes, extension).ok_or("Failed to detect the image format")?;
+
+        let first = match animation::frames(&bytes, format)? {
+            Some(mut frames) if capacity > 0 => frames.next().ok_or("Animation has no frames")??,
+            _ => return Self::from_bytes(&bytes, extension)
+        };
+
+        let (loop_count, orientation) = (animation::loop_count(&bytes, format), orientation(&bytes));
+        let stream = FrameStream::new(Arc::from(bytes), format, capacity, 1);
+
+        Ok(Self { frames: vec![first], loop_count, orientation, stream: Some(stream) })
+    }
+}
+
+/// Writes every frame of `file` to `directory` as PNGs numbered after `stem`, as they are stored without the orientation applied.
+/// Each frame is written as soon as it is decoded, so only one of them is in memory at a time.
+pub fn export_frames(file: &Path, directory: &Path, stem: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(file)?;
+    let extension = file.extension().unwrap_or_default().to_str().unwrap_or_default();
+    let format = format::detect(&bytes, extension).ok_or("Failed to detect the image format")?;
+
+    let mut sidecar = export::Sidecar::open(directory, stem)?;
+
+    if let Some(frames) = animation::frames(&bytes, format)? {
+        for (index, frame) in frames.enumerate() { sidecar.write_frame(index, &frame?)?; }
+    } else {
+        let mut reader = ImageReader::new(Cursor::new(&bytes));
+        reader.set_format(format);
+        sidecar.write_frame(0, &image::Frame::new(reader.decode()?.into_rgba8()))?;
+    }
+
+    sidecar.finish()
+}
+
+/// The names the frames of each of `files` are exported under, files with the same stem
+/// as one before them get a number added so they don't overwrite each other
+pub fn export_stems(files: &[PathBuf]) -> Vec<String> {
+    let mut taken = HashSet::new();
+
+    files.iter()
+        .map(|file| {
+            let stem = file.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+            let mut unique = stem.clone();
+            let mut number = 1;
+            while !taken.insert(unique.clone()) {
+                number += 1;
+                unique = format!("{stem}-{number}");
+            }
+
+            unique
+        })
+        .collect()
+}
+
+fn orientation(bytes: &[u8]) -> Option<Orientation> {
+    if config::get().options.no_auto_orient { None } else { Orientation::from_exif(bytes) }
+}
+
+/// A frame that was asked for, or why it can't be shown
+enum Fetched {
+    Frame(Frame),
+    Pending,
+    /// The frame is past the end of the animation, which has this many frames
+    End(usize),
+    Failed
+}
+
 impl SmartPaintable {
-    /// The loading consists of decoding the gif with a GIFDecoder, then storing
-    /// the frames so that the paintable can render them.
+    /// A placeholder for an image that couldn't be loaded
+    pub fn from_error(error: &str) -> Self {
+        let paintable = Self::default();
+        paintable.set_error(error);
+        paintable
+    }
 
-    pub fn load_from_bytes(&self, bytes: &[u8], extension: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// Installs an image that was decoded beforehand, possibly on another thread
+    pub fn load_decoded(&self, decoded: Decoded) {
         let imp = self.imp();
         imp.current_idx.set(0);
+        imp.pending_idx.set(None);
+        imp.error.replace(None);
 
         if let Some(source_id) = imp.timeout_source_id.take() { source_id.remove() }
 
-        let is_animated = extension == "gif";
+        let mut frames = decoded.frames
+            .into_iter()
+            .map(Frame::from)
+            .collect::<Vec<Frame>>();
 
-        let read = Cursor::new(bytes);
-        if is_animated {
-            let decoder = GifDecoder::new(read)?;
+        let first_duration = frames.first().map(|frame| frame.frame_duration).unwrap_or_default();
+        imp.next_frame.replace(frames.first().cloned());
 
-            let frames = decoder
-                .into_frames()
-                .collect_frames()?
-                .into_iter()
-                .map(Frame::from)
-                .collect::<Vec<Frame>>();
+        // a streamed animation only keeps the frame that is shown
+        if decoded.stream.is_some() { frames.clear() }
+        imp.frames.replace((!frames.is_empty()).then_some(frames));
+        imp.stream.replace(decoded.stream);
 
-            imp.frames.replace(Some(frames));
-        } else {
-            let mut reader = ImageReader::new(read);
-            reader.set_format(
-                image::ImageFormat::from_extension(extension)
-                .expect("Failed to guess image format from extension"));
-            let frame = image::Frame::new(reader
-                .decode()
-                .expect("Failed to parse image")
-                .into_rgba8());
-
-            imp.frames.replace(Some(vec![Frame::from(frame)]));
+        imp.loop_count.set(decoded.loop_count);
+        imp.loops_played.set(0);
+        imp.is_backward.set(imp.is_reversed.get());
+
+        let orientation = decoded.orientation;
+        imp.is_auto_oriented.set(orientation.is_some_and(|o| o != Orientation::default()));
+        imp.auto_orientation.set(orientation.unwrap_or_default());
+        self.set_orientation(orientation.unwrap_or_default());
+
+        self.invalidate_size();
+        self.invalidate_contents();
+
+        if imp.is_playing.get() && self.is_animated() {
+            self.schedule_next_frame(first_duration.div_f64(imp.speed.get()));
         }
+    }
 
-        self.setup_next_frame();
+    pub fn load_from_bytes(&self, bytes: &[u8], extension: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.load_decoded(Decoded::from_bytes(bytes, extension)?);
         Ok(())
     }
 
-    pub fn load_from_file(&self, file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>>  {
-        let (bytes, _) = gio::File::for_path(file).load_contents(gio::Cancellable::NONE).unwrap();
-        self.load_from_bytes(&bytes, file.extension().unwrap_or_default().to_str().unwrap())
+    pub fn load_from_file(&self, file: &Path) -> Result<(), Box<dyn std::error::Error>>  {
+        self.load_decoded(Decoded::from_file(file)?);
+        Ok(())
     }
 
-    fn setup_next_frame(&self) {
+    /// Roughly how much memory the decoded frames take up
+    pub fn byte_size(&self) -> usize {
         let imp = self.imp();
-        let idx = imp.current_idx.get();
-        let frames_ref = imp.frames.borrow();
+        let texture_size = |texture: &gdk::Texture| texture.width() as usize * texture.height() as usize * 4;
+
+        // a stream holds the shown frame and at most its capacity of frames before and after that
+        if let Some(stream) = &*imp.stream.borrow() {
+            let frame_size = imp.next_frame.borrow().as_ref().map(|frame| texture_size(&frame.texture)).unwrap_or(0);
+            return frame_size * (2 * stream.capacity() + 1)
+        }
+
+        imp.frames.borrow()
+            .as_ref()
+            .map(|frames| frames.iter().map(|frame| texture_size(&frame.texture)).sum())
+            .unwrap_or(0)
+    }
+
+    /// Writes the shown frame to `directory` as a PNG named after `stem` and its index
+    pub fn export_frame(&self, directory: &Path, stem: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let frame = self.imp().next_frame.borrow().clone().ok_or("Nothing to export")?;
+
+        let mut sidecar = export::Sidecar::open(directory, stem)?;
+        frame.texture.save_to_png(sidecar.add(self.frame_index(), frame.frame_duration))?;
+        sidecar.finish()
+    }
+
+    /// Goes back to how the image looked when it was loaded: oriented by its EXIF data,
+    /// paused on the first frame and played forward at normal speed
+    pub fn reset_view(&self) {
+        let imp = self.imp();
+        self.pause();
+        imp.speed.set(1.0);
+        imp.is_reversed.set(false);
+        imp.is_ping_pong.set(false);
+        imp.is_backward.set(false);
+        imp.loops_played.set(0);
+        self.set_orientation(imp.auto_orientation.get());
+
+        imp.pending_idx.set(None);
+        if let Some(source_id) = imp.timeout_source_id.take() { source_id.remove() }
+        if imp.current_idx.get() != 0 { self.show_frame(0) }
+    }
+
+    /// Whether nothing was loaded into the paintable yet
+    pub fn is_empty(&self) -> bool {
+        self.imp().next_frame.borrow().is_none() && self.imp().error.borrow().is_none()
+    }
+
+    /// Whether the image has more than one frame
+    pub fn is_animated(&self) -> bool {
+        let imp = self.imp();
+        imp.stream.borrow().is_some() || imp.frames.borrow().as_ref().is_some_and(|frames| frames.len() > 1)
+    }
+
+    /// The index of the frame that is shown
+    pub fn frame_index(&self) -> usize {
+        self.imp().current_idx.get()
+    }
+
+    /// How many frames the image has, a stream only knows once it decoded all of them
+    pub fn frame_count(&self) -> Option<usize> {
+        let imp = self.imp();
+        match &*imp.stream.borrow() {
+            Some(stream) => stream.frame_count(),
+            None => imp.frames.borrow().as_ref().map(|frames| frames.len())
+        }
+    }
 
-        if !imp.is_playing.get() && idx > 0 { return }
-        if !imp.timeout_source_id.borrow().is_none() { return }
+    fn fetch_frame(&self, index: usize) -> Fetched {
+        let imp = self.imp();
+
+        if let Some(stream) = &mut *imp.stream.borrow_mut() {
+            return match stream.frame(index) {
+                Next::Frame(frame) => Fetched::Frame(Frame::from(frame)),
+                Next::Pending => Fetched::Pending,
+                Next::End(count) => Fetched::End(count),
+                Next::Failed => Fetched::Failed
+            }
+        }
+
+        match &*imp.frames.borrow() {
+            Some(frames) if index < frames.len() => Fetched::Frame(frames[index].clone()),
+            Some(frames) => Fetched::End(frames.len()),
+            None => Fetched::Failed
+        }
+    }
 
-        // if we have stored no frames then we early return early
-        // and instead render a default frame in `imp::SmartPaintable::snapshot`
-        let frames = match &*frames_ref {
-            Some(frames) => frames,
-            None => return,
+    /// Shows frame `index`, or the last frame for `LAST_FRAME`, as soon as it is decoded
+    fn show_frame(&self, index: usize) {
+        let imp = self.imp();
+        imp.pending_idx.set(None);
+
+        if let Some(source_id) = imp.timeout_source_id.take() { source_id.remove() }
+
+        let index = match (index, self.frame_count()) {
+            (LAST_FRAME, Some(count)) => count - 1,
+            _ => index
         };
 
+        match self.fetch_frame(index) {
+            Fetched::Frame(frame) => {
+                let previous_size = imp.image_size();
+                let duration = frame.frame_duration;
+                imp.next_frame.replace(Some(frame));
+                imp.current_idx.set(index);
 
-        let next_frame = frames.get(idx).unwrap();
-        imp.next_frame.replace(Some(next_frame.texture.clone()));
+                if imp.image_size() != previous_size { self.invalidate_size() }
 
-        // invalidate the contents so that the new frame will be rendered
-        self.invalidate_contents();
+                // invalidate the contents so that the new frame will be rendered
+                self.invalidate_contents();
+
+                if imp.is_playing.get() && self.is_animated() {
+                    self.schedule_next_frame(duration.div_f64(imp.speed.get()));
+                }
+            }
+
+            Fetched::Pending => {
+                imp.pending_idx.set(Some(index));
+                self.schedule_next_frame(STREAM_RETRY);
+            }
+
+            // a stream only finds out how many frames there are when it gets past the last one
+            Fetched::End(count) => {
+                if index == LAST_FRAME { return self.show_frame(count - 1) }
+
+                // only `step` goes past the end while paused, it wraps around without counting a loop
+                if !imp.is_playing.get() { return self.show_frame(0) }
+                if let Some(next) = self.next_index() { self.show_frame(next) }
+            }
+
+            // there is nothing more to play
+            Fetched::Failed => imp.is_playing.set(false)
+        }
+    }
+
+    /// The frame after the shown one in the direction the animation plays,
+    /// `None` once it was played as often as it asks for
+    fn next_index(&self) -> Option<usize> {
+        let imp = self.imp();
+        let idx = imp.current_idx.get();
+        let is_backward = imp.is_backward.get();
+
+        let is_at_end = if is_backward {
+            idx == 0
+        } else {
+            self.frame_count().is_some_and(|count| idx + 1 >= count)
+        };
+
+        if !is_at_end { return Some(if is_backward { idx - 1 } else { idx + 1 }) }
+
+        imp.loops_played.set(imp.loops_played.get() + 1);
 
+        // stay on the last frame once the animation was played as often as it asks for
+        if imp.loop_count.get().is_some_and(|count| imp.loops_played.get() >= count) { return None }
+
+        if imp.is_ping_pong.get() {
+            imp.is_backward.set(!is_backward);
+            return Some(if is_backward { idx + 1 } else { idx.saturating_sub(1) })
+        }
+
+        Some(if is_backward { LAST_FRAME } else { 0 })
+    }
+
+    fn schedule_next_frame(&self, delay: Duration) {
         let update_next_frame_callback = glib::clone!(
             @weak self as paintable => move || {
                 paintable.imp().timeout_source_id.take();
-                paintable.setup_next_frame();
+                paintable.play_next_frame();
             }
         );
 
-        let source_id = glib::timeout_add_local_once(
-            next_frame.frame_duration, update_next_frame_callback);
-        imp.timeout_source_id.replace(Some(source_id));
+        let source_id = glib::timeout_add_local_once(delay, update_next_frame_callback);
+        self.imp().timeout_source_id.replace(Some(source_id));
+    }
+
+    fn play_next_frame(&self) {
+        let imp = self.imp();
+
+        if let Some(index) = imp.pending_idx.take() { return self.show_frame(index) }
+        if !imp.is_playing.get() { return }
+
+        if let Some(index) = self.next_index() { self.show_frame(index) }
+    }
 
-        // setup the index for the next call to setup_next_frame
-        let mut new_idx = idx + 1;
-        if new_idx >= frames.len() { new_idx = 0 }
-        imp.current_idx.set(new_idx);
+    /// How often the animation got to its end since it was loaded or restarted
+    pub fn loops_played(&self) -> u32 {
+        self.imp().loops_played.get()
     }
 
     pub fn is_playing(&self) -> bool {
@@ -108,21 +392,129 @@ impl SmartPaintable {
     }
 
     pub fn resume(&self) {
-        self.imp().is_playing.set(true);
-        self.setup_next_frame();
+        let imp = self.imp();
+        if imp.is_playing.replace(true) || !self.is_animated() { return }
+
+        // an animation that ran out of loops starts over
+        if imp.loop_count.get().is_some_and(|count| imp.loops_played.get() >= count) {
+            return self.restart()
+        }
+
+        if imp.timeout_source_id.borrow().is_none() { self.play_next_frame() }
     }
 
     pub fn pause(&self) {
         self.imp().is_playing.set(false);
     }
 
+    /// Goes back to where the animation starts in the direction it plays in
     pub fn restart(&self) {
-        self.imp().current_idx.set(0);
+        let imp = self.imp();
+        imp.loops_played.set(0);
+        imp.is_backward.set(imp.is_reversed.get());
+
+        self.show_frame(if imp.is_reversed.get() { LAST_FRAME } else { 0 });
+    }
+
+    /// Pauses and shows the next or the previous frame, wrapping around at the ends
+    pub fn step(&self, is_forward: bool) {
+        if !self.is_animated() { return }
+        self.pause();
+
+        let idx = self.imp().current_idx.get();
+        let index = match (is_forward, self.frame_count()) {
+            (true, Some(count)) if idx + 1 >= count => 0,
+            (true, _) => idx + 1,
+            (false, _) if idx == 0 => LAST_FRAME,
+            (false, _) => idx - 1
+        };
+
+        self.show_frame(index);
+    }
+
+    pub fn first_frame(&self) {
+        if self.is_animated() { self.show_frame(0) }
+    }
+
+    pub fn last_frame(&self) {
+        if self.is_animated() { self.show_frame(LAST_FRAME) }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.imp().speed.get()
+    }
+
+    /// Only takes effect from the next frame on
+    pub fn set_speed(&self, speed: f64) {
+        self.imp().speed.set(speed);
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        self.imp().is_reversed.get()
+    }
+
+    pub fn set_reversed(&self, is_reversed: bool) {
+        self.imp().is_reversed.set(is_reversed);
+        self.imp().is_backward.set(is_reversed);
+    }
+
+    pub fn is_ping_pong(&self) -> bool {
+        self.imp().is_ping_pong.get()
+    }
+
+    pub fn set_ping_pong(&self, is_ping_pong: bool) {
+        self.imp().is_ping_pong.set(is_ping_pong);
+        if !is_ping_pong { self.imp().is_backward.set(self.imp().is_reversed.get()) }
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.imp().error.borrow().clone()
+    }
+
+    pub fn set_error(&self, error: &str) {
+        self.imp().error.replace(Some(error.to_string()));
+
+        self.invalidate_size();
+        self.invalidate_contents();
+    }
+
+    /// The size of the image itself, without the zoom applied
+    pub fn image_width(&self) -> i32 {
+        self.imp().image_size().map(|(width, _)| width).unwrap_or(-1)
+    }
+
+    pub fn image_height(&self) -> i32 {
+        self.imp().image_size().map(|(_, height)| height).unwrap_or(-1)
+    }
+
+    pub fn set_scale(&self, scale: f64) {
+        if self.imp().scale.get() == scale { return }
+
+        self.imp().scale.set(scale);
+        self.invalidate_size();
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.imp().orientation.get()
+    }
+
+    /// Whether the orientation was taken from the EXIF data of the image
+    pub fn is_auto_oriented(&self) -> bool {
+        self.imp().is_auto_oriented.get()
+    }
+
+    pub fn set_orientation(&self, orientation: Orientation) {
+        let previous = self.imp().orientation.replace(orientation);
+
+        if previous.is_transposed() != orientation.is_transposed() { self.invalidate_size() }
+        self.invalidate_contents();
     }
 
     pub fn rotate(&self, angle: i32) {
-        self.imp().rotation.set(angle);
+        self.set_orientation(self.orientation().rotate(angle));
     }
 
-    pub fn flip(&self, is_horizontal: bool) { }
+    pub fn flip(&self, is_horizontal: bool) {
+        self.set_orientation(self.orientation().flip(is_horizontal));
+    }
 }
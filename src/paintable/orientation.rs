@@ -0,0 +1,79 @@
+use image::DynamicImage;
+
+
+/// One of the 8 ways an image can be placed using quarter turns and mirroring.
+/// The image is mirrored first and rotated afterwards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    /// Clockwise quarter turns
+    pub turns: u8,
+    /// Mirrored along the vertical axis
+    pub is_mirrored: bool
+}
+
+impl Orientation {
+    /// Reads the orientation from the EXIF data embedded in an image
+    pub fn from_exif(bytes: &[u8]) -> Option<Self> {
+        let exif = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(bytes))
+            .ok()?;
+
+        let value = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+            .value
+            .get_uint(0)?;
+
+        let (turns, is_mirrored) = match value {
+            1 => (0, false),
+            2 => (0, true),
+            3 => (2, false),
+            4 => (2, true),
+            5 => (3, true),
+            6 => (1, false),
+            7 => (1, true),
+            8 => (3, false),
+            _ => return None
+        };
+
+        Some(Self { turns, is_mirrored })
+    }
+
+    pub fn rotate(self, angle: i32) -> Self {
+        Self {
+            turns: (self.turns as i32 + angle / 90).rem_euclid(4) as u8,
+            is_mirrored: self.is_mirrored
+        }
+    }
+
+    /// Flips the image as it is currently shown on screen
+    pub fn flip(self, is_horizontal: bool) -> Self {
+        // mirroring a rotated image is the same as mirroring it first and
+        // rotating it the other way around, a vertical flip adds a half turn
+        let turns = if is_horizontal { 4 - self.turns } else { 6 - self.turns };
+
+        Self {
+            turns: turns % 4,
+            is_mirrored: !self.is_mirrored
+        }
+    }
+
+    /// Bakes the orientation into the pixels of an image
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = if self.is_mirrored { image.fliph() } else { image };
+
+        match self.turns {
+            1 => image.rotate90(),
+            2 => image.rotate180(),
+            3 => image.rotate270(),
+            _ => image
+        }
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.turns as f32 * 90.0
+    }
+
+    /// Whether width and height are swapped
+    pub fn is_transposed(&self) -> bool {
+        self.turns % 2 == 1
+    }
+}
@@ -0,0 +1,135 @@
+use std::{collections::VecDeque, sync::{mpsc, Arc}};
+
+use image::ImageFormat;
+
+use super::animation;
+
+
+enum Message {
+    Frame(usize, image::Frame),
+    /// Every frame was sent, the ones after this start over from the beginning
+    End(usize)
+}
+
+pub enum Next {
+    Frame(image::Frame),
+    /// The decoder hasn't caught up with playback yet
+    Pending,
+    /// The frame that was asked for is past the end, the animation has this many frames
+    End(usize),
+    /// The decoder stopped because a frame couldn't be decoded
+    Failed
+}
+
+/// Decodes the frames of an animation on another thread while it plays,
+/// staying at most `capacity` frames ahead of playback
+pub struct FrameStream {
+    bytes: Arc<[u8]>,
+    format: ImageFormat,
+    capacity: usize,
+    receiver: mpsc::Receiver<Message>,
+    /// The frame the decoder sends next
+    position: usize,
+    /// Only known once the decoder went through the whole animation
+    frame_count: Option<usize>,
+    /// The last `capacity` frames that were received, so going back doesn't have to start over every frame
+    history: VecDeque<(usize, image::Frame)>
+}
+
+impl FrameStream {
+    pub fn new(bytes: Arc<[u8]>, format: ImageFormat, capacity: usize, start: usize) -> Self {
+        let receiver = spawn(bytes.clone(), format, capacity, start);
+
+        Self {
+            bytes,
+            format,
+            capacity,
+            receiver,
+            position: start,
+            frame_count: None,
+            history: VecDeque::with_capacity(capacity)
+        }
+    }
+
+    /// Continues at frame `index`, the frames decoded ahead so far are thrown away
+    pub fn seek(&mut self, index: usize) {
+        // dropping the old receiver stops its thread
+        self.receiver = spawn(self.bytes.clone(), self.format, self.capacity, index);
+        self.position = index;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn frame_count(&self) -> Option<usize> {
+        self.frame_count
+    }
+
+    /// Takes frame `index` from the decoder. Going forward is cheap, and so is going back to one of the last few frames.
+    /// An earlier frame makes the decoder start over, a few frames before it so that going further back is cheap again.
+    pub fn frame(&mut self, index: usize) -> Next {
+        if let Some((_, frame)) = self.history.iter().find(|(i, _)| *i == index) { return Next::Frame(frame.clone()) }
+
+        // the first frame follows the end of the animation
+        let is_wrapping = index == 0 && self.frame_count == Some(self.position);
+        if index < self.position && !is_wrapping { self.seek(index.saturating_sub(self.capacity - 1)) }
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Frame(frame_index, frame)) => {
+                    self.position = frame_index + 1;
+
+                    // the frames on the way are kept too, for playing backward
+                    if !self.history.iter().any(|(i, _)| *i == frame_index) {
+                        if self.history.len() >= self.capacity { self.history.pop_front(); }
+                        self.history.push_back((frame_index, frame.clone()));
+                    }
+
+                    if frame_index == index { return Next::Frame(frame) }
+                }
+
+                Ok(Message::End(count)) => {
+                    self.position = 0;
+                    self.frame_count = Some(count);
+                    if index >= count { return Next::End(count) }
+                }
+
+                Err(mpsc::TryRecvError::Empty) => return Next::Pending,
+                Err(mpsc::TryRecvError::Disconnected) => return Next::Failed
+            }
+        }
+    }
+}
+
+fn spawn(bytes: Arc<[u8]>, format: ImageFormat, capacity: usize, start: usize) -> mpsc::Receiver<Message> {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    std::thread::spawn(move || decode(&bytes, format, start, &sender));
+    receiver
+}
+
+fn decode(bytes: &[u8], format: ImageFormat, start: usize, sender: &mpsc::SyncSender<Message>) {
+    let mut start = start;
+
+    loop {
+        let Ok(Some(frames)) = animation::frames(bytes, format) else { return };
+
+        let mut count = 0;
+        for (index, frame) in frames.enumerate() {
+            // a broken frame ends the animation early
+            let Ok(frame) = frame else { break };
+            count = index + 1;
+
+            // frames are drawn on top of the previous ones, so seeking still has to decode those
+            if index < start { continue }
+
+            // the channel is full until playback catches up, when it is gone we are done
+            if sender.send(Message::Frame(index, frame)).is_err() { return }
+        }
+
+        if count == 0 { return }
+
+        start = 0;
+        if sender.send(Message::End(count)).is_err() { return }
+    }
+}
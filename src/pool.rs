@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Condvar, Mutex}
+};
+
+
+/// Files waiting to be worked on, the prioritized ones are taken first
+#[derive(Debug, Default)]
+struct Queue {
+    files: VecDeque<PathBuf>,
+    priority: VecDeque<PathBuf>,
+    /// Everything that was queued but not taken by a worker yet
+    pending: HashSet<PathBuf>
+}
+
+impl Queue {
+    fn take(&mut self) -> Option<PathBuf> {
+        // a file can be in both queues, whichever comes first wins
+        while let Some(file) = self.priority.pop_front().or_else(|| self.files.pop_front()) {
+            if self.pending.remove(&file) { return Some(file) }
+        }
+
+        None
+    }
+}
+
+/// Runs a job for every queued file on a set of worker threads
+#[derive(Debug)]
+pub struct Pool<T> {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    receiver: mpsc::Receiver<(PathBuf, T)>
+}
+
+impl<T: Send + 'static> Pool<T> {
+    /// Starts `threads` workers running `job`, or one for every cpu if it is 0
+    pub fn new(threads: usize, job: impl Fn(&Path) -> T + Send + Sync + 'static) -> Self {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            threads => threads
+        };
+
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let job = Arc::new(job);
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..threads {
+            let queue = queue.clone();
+            let job = job.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || work(&queue, &*job, &sender));
+        }
+
+        Self { queue, receiver }
+    }
+
+    pub fn push(&self, file: PathBuf) {
+        let (queue, condvar) = &*self.queue;
+        let mut guard = queue.lock().unwrap();
+        if !guard.pending.insert(file.clone()) { return }
+
+        guard.files.push_back(file);
+        std::mem::drop(guard);
+        condvar.notify_one();
+    }
+
+    /// Forgets every file that no worker has taken yet
+    pub fn clear(&self) {
+        let (queue, _) = &*self.queue;
+        *queue.lock().unwrap() = Queue::default();
+    }
+
+    /// Moves `files` to the front of the queue, replacing the previously prioritized ones
+    pub fn prioritize(&self, files: impl IntoIterator<Item = PathBuf>) {
+        let (queue, _) = &*self.queue;
+        let mut guard = queue.lock().unwrap();
+
+        let files = files.into_iter()
+            .filter(|file| guard.pending.contains(file))
+            .collect();
+        guard.priority = files;
+    }
+
+    /// Results that are done since the last call, without waiting for more
+    pub fn finished(&self) -> mpsc::TryIter<'_, (PathBuf, T)> {
+        self.receiver.try_iter()
+    }
+}
+
+fn work<T>(queue: &(Mutex<Queue>, Condvar), job: &dyn Fn(&Path) -> T, sender: &mpsc::Sender<(PathBuf, T)>) {
+    let (queue, condvar) = queue;
+
+    loop {
+        let mut guard = queue.lock().unwrap();
+        let file = loop {
+            if let Some(file) = guard.take() { break file }
+            guard = condvar.wait(guard).unwrap();
+        };
+        std::mem::drop(guard);
+
+        let result = job(&file);
+
+        // the receiving side is gone once the application quits
+        if sender.send((file, result)).is_err() { return }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::metadata;
+
+use std::{cmp::Ordering, hash::BuildHasher, path::PathBuf};
+
+use image::ImageReader;
+use serde::Deserialize;
+
+
+const SORT_KEYS: &[(&str, SortKey)] = &[
+    ("none", SortKey::None),
+    ("name", SortKey::Name),
+    ("modified", SortKey::Modified),
+    ("mtime", SortKey::Modified),
+    ("size", SortKey::Size),
+    ("date", SortKey::Date),
+    ("dimensions", SortKey::Dimensions),
+    ("shuffle", SortKey::Shuffle)
+];
+
+/// What the file list is ordered by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SortKey {
+    /// Keep the order the files were given in
+    #[default]
+    None,
+    /// Numbers in names are compared by their value, so `2.png` comes before `10.png`
+    Name,
+    Modified,
+    /// The size of the file, not of the image
+    Size,
+    /// When the photo was taken according to its EXIF data
+    Date,
+    /// The number of pixels
+    Dimensions,
+    Shuffle
+}
+
+impl SortKey {
+    /// The key after this one, going through the ones that actually sort
+    pub fn next(&self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Modified,
+            SortKey::Modified => SortKey::Size,
+            SortKey::Size => SortKey::Date,
+            SortKey::Date => SortKey::Dimensions,
+            _ => SortKey::Name
+        }
+    }
+
+    /// The first of its names, aliases come after it
+    pub fn name(&self) -> &'static str {
+        SORT_KEYS.iter()
+            .find(|(_, key)| key == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        SORT_KEYS.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, key)| *key)
+            .ok_or_else(|| format!("unknown sort key `{name}`"))
+    }
+}
+
+impl TryFrom<String> for SortKey {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+/// The indices of `files` in the order `key` puts them in
+pub fn order(files: &[PathBuf], key: SortKey, is_reversed: bool) -> Vec<usize> {
+    let mut order = (0..files.len()).collect::<Vec<usize>>();
+
+    match key {
+        SortKey::None => {}
+
+        SortKey::Name => order.sort_by(|a, b| {
+            let name = |index: usize| files[index].file_name().unwrap_or_default().to_string_lossy();
+            natural_cmp(&name(*a), &name(*b))
+                .then_with(|| natural_cmp(&files[*a].to_string_lossy(), &files[*b].to_string_lossy()))
+        }),
+
+        SortKey::Modified => sort_by_keys(&mut order, files.iter()
+            .map(|file| std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+            .collect()),
+
+        SortKey::Size => sort_by_keys(&mut order, files.iter()
+            .map(|file| std::fs::metadata(file).map(|metadata| metadata.len()).ok())
+            .collect()),
+
+        SortKey::Date => sort_by_keys(&mut order, files.iter()
+            .map(|file| metadata::date_taken(file))
+            .collect()),
+
+        SortKey::Dimensions => sort_by_keys(&mut order, files.iter()
+            .map(|file| {
+                let (width, height) = ImageReader::open(file).ok()?.with_guessed_format().ok()?.into_dimensions().ok()?;
+                Some(width as u64 * height as u64)
+            })
+            .collect()),
+
+        SortKey::Shuffle => shuffle(&mut order)
+    }
+
+    if is_reversed { order.reverse() }
+    order
+}
+
+/// The items of `items` in `order`
+pub fn apply<T: Clone>(items: &[T], order: &[usize]) -> Vec<T> {
+    order.iter().map(|index| items[*index].clone()).collect()
+}
+
+/// Stable, so files with the same key keep their order, and files without one go last
+fn sort_by_keys<K: Ord>(order: &mut [usize], keys: Vec<Option<K>>) {
+    order.sort_by(|a, b| {
+        let (a, b) = (&keys[*a], &keys[*b]);
+        a.is_none().cmp(&b.is_none()).then_with(|| a.cmp(b))
+    });
+}
+
+/// Compares names the way people read them, runs of digits by their value and letters ignoring case
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        let ordering = match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+            }
+
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                a.next();
+                b.next();
+                ordering
+            }
+        };
+
+        if ordering != Ordering::Equal { return ordering }
+    }
+}
+
+/// Takes a run of digits without its leading zeros
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) { number.push(digit) }
+
+    number.trim_start_matches('0').to_string()
+}
+
+/// Fisher-Yates with the random keys of the standard library, so we get by without a dependency
+pub fn shuffle<T>(items: &mut [T]) {
+    let state = std::collections::hash_map::RandomState::new();
+    for i in (1..items.len()).rev() {
+        let j = (state.hash_one(i) % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        assert_eq!(natural_cmp("2.png", "10.png"), Ordering::Less);
+        assert_eq!(natural_cmp("a10b", "a9c"), Ordering::Greater);
+        assert_eq!(natural_cmp("a2b", "a10a"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros_are_ignored() {
+        assert_eq!(natural_cmp("img007", "img7"), Ordering::Equal);
+        assert_eq!(natural_cmp("01", "2"), Ordering::Less);
+        assert_eq!(natural_cmp("0", "00"), Ordering::Equal);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+        assert_eq!(natural_cmp("ABC", "abc"), Ordering::Equal);
+    }
+
+    #[test]
+    fn shorter_names_come_first() {
+        assert_eq!(natural_cmp("a", "ab"), Ordering::Less);
+        assert_eq!(natural_cmp("a1", "a"), Ordering::Greater);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config;
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        gif::{GifEncoder, Repeat},
+        jpeg::JpegEncoder,
+        png::{self, PngEncoder},
+        webp::WebPEncoder
+    },
+    DynamicImage, Frame, ImageFormat
+};
+use serde::Deserialize;
+
+
+const THUMBNAIL_FORMATS: &[(&str, ThumbnailFormat)] = &[
+    ("png", ThumbnailFormat::Png),
+    ("webp", ThumbnailFormat::WebP),
+    ("jpeg", ThumbnailFormat::Jpeg),
+    ("avif", ThumbnailFormat::Avif)
+];
+
+/// The formats cached thumbnails can be stored in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ThumbnailFormat {
+    Png,
+    /// Always lossless, the image crate can't encode lossy WebP
+    WebP,
+    /// Has no alpha channel, transparent images get a black background
+    Jpeg,
+    /// The smallest files, but by far the slowest to encode
+    #[default]
+    Avif
+}
+
+impl ThumbnailFormat {
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            ThumbnailFormat::Png => ImageFormat::Png,
+            ThumbnailFormat::WebP => ImageFormat::WebP,
+            ThumbnailFormat::Jpeg => ImageFormat::Jpeg,
+            ThumbnailFormat::Avif => ImageFormat::Avif
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.image_format().extensions_str().first().unwrap()
+    }
+}
+
+impl std::str::FromStr for ThumbnailFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        THUMBNAIL_FORMATS.iter()
+            .find(|(format, _)| *format == name)
+            .map(|(_, format)| *format)
+            .ok_or_else(|| format!("unknown thumbnail format `{name}`"))
+    }
+}
+
+impl TryFrom<String> for ThumbnailFormat {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+/// Animated thumbnails are always stored as GIF, the only animated format the image crate can encode
+pub const ANIMATION_EXTENSION: &str = "gif";
+
+pub fn save_animation(frames: Vec<Frame>, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // the GIF encoder goes from 1 to 30, the config from 1 to 10
+    let speed = config::get().settings.thumbnail_speed.clamp(1, 10) as i32 * 3;
+
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), speed);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames)?;
+
+    Ok(())
+}
+
+/// Encodes a thumbnail with the format, quality and speed from the config
+pub fn save(image: &DynamicImage, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = &config::get().settings;
+    let quality = settings.thumbnail_quality.clamp(1, 100);
+    let speed = settings.thumbnail_speed.clamp(1, 10);
+    let writer = BufWriter::new(File::create(path)?);
+
+    // not every encoder supports every color type, but all of them support 8 bit RGB
+    let image = match settings.thumbnail_format {
+        ThumbnailFormat::Jpeg => DynamicImage::from(image.to_rgb8()),
+        _ => DynamicImage::from(image.to_rgba8())
+    };
+
+    match settings.thumbnail_format {
+        ThumbnailFormat::Png => {
+            let compression = match speed {
+                1..=3 => png::CompressionType::Best,
+                4..=7 => png::CompressionType::Default,
+                _ => png::CompressionType::Fast
+            };
+
+            image.write_with_encoder(PngEncoder::new_with_quality(writer, compression, png::FilterType::Adaptive))?
+        }
+
+        ThumbnailFormat::WebP => image.write_with_encoder(WebPEncoder::new_lossless(writer))?,
+
+        ThumbnailFormat::Jpeg => image.write_with_encoder(JpegEncoder::new_with_quality(writer, quality))?,
+
+        ThumbnailFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(writer, speed, quality))?
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH
+};
+
+use gtk::glib;
+use image::DynamicImage;
+
+
+/// The size directories and the largest thumbnail each of them holds
+const SIZES: &[(&str, u32)] = &[
+    ("normal", 128),
+    ("large", 256),
+    ("x-large", 512),
+    ("xx-large", 1024)
+];
+
+/// Thumbnails in here are shared with other applications, following the
+/// freedesktop.org thumbnail managing standard
+fn directory() -> PathBuf {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => std::env::home_dir().unwrap().join(".cache")
+    }.join("thumbnails")
+}
+
+/// The smallest size directory whose thumbnails are at least `size` large
+fn size_directory(size: u32) -> PathBuf {
+    let (name, _) = SIZES.iter()
+        .find(|(_, largest)| *largest >= size)
+        .unwrap_or(SIZES.last().unwrap());
+
+    directory().join(name)
+}
+
+/// Every application records the files it failed to thumbnail in its own directory
+fn fail_directory() -> PathBuf {
+    directory().join("fail").join(concat!("ponyview-", env!("CARGO_PKG_VERSION")))
+}
+
+fn uri(file: &Path) -> Option<String> {
+    let file = std::fs::canonicalize(file).ok()?;
+    glib::filename_to_uri(file, None).ok().map(|uri| uri.to_string())
+}
+
+/// Thumbnails are named after the MD5 hash of the URI of their file
+fn file_name(uri: &str) -> String {
+    format!("{:x}.png", md5::compute(uri.as_bytes()))
+}
+
+fn modified(file: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(file).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn read_text_chunks(path: &Path) -> Option<HashMap<String, String>> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path).ok()?));
+    let reader = decoder.read_info().ok()?;
+    let info = reader.info();
+
+    let latin1 = info.uncompressed_latin1_text.iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()));
+    let utf8 = info.utf8_text.iter()
+        .filter_map(|chunk| Some((chunk.keyword.clone(), chunk.get_text().ok()?)));
+
+    Some(latin1.chain(utf8).collect())
+}
+
+/// Whether `path` was made from `file` as it is right now
+fn is_valid(path: &Path, file: &Path, uri: &str) -> bool {
+    let Some(text) = read_text_chunks(path) else { return false };
+    let Some(modified) = modified(file) else { return false };
+
+    let is_same_size = match (text.get("Thumb::Size"), std::fs::metadata(file)) {
+        (Some(size), Ok(metadata)) => *size == metadata.len().to_string(),
+        _ => true
+    };
+
+    text.get("Thumb::URI").is_some_and(|u| u == uri)
+        && text.get("Thumb::MTime").is_some_and(|m| *m == modified.to_string())
+        && is_same_size
+}
+
+/// Finds a thumbnail of `file` that is `size` large and still up to date
+pub fn find(file: &Path, size: u32) -> Option<PathBuf> {
+    let uri = uri(file)?;
+    let path = size_directory(size).join(file_name(&uri));
+
+    is_valid(&path, file, &uri).then_some(path)
+}
+
+/// Whether creating a thumbnail of `file` failed before and it hasn't changed since
+pub fn has_failed(file: &Path) -> bool {
+    let Some(uri) = uri(file) else { return false };
+    is_valid(&fail_directory().join(file_name(&uri)), file, &uri)
+}
+
+pub fn save(file: &Path, image: &DynamicImage, size: u32) -> Result<(), Box<dyn std::error::Error>> {
+    write(&size_directory(size), file, image)
+}
+
+/// Records that `file` couldn't be thumbnailed, so nobody tries again until it changes
+pub fn save_failure(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    write(&fail_directory(), file, &DynamicImage::new_rgba8(1, 1))
+}
+
+fn write(directory: &Path, file: &Path, image: &DynamicImage) -> Result<(), Box<dyn std::error::Error>> {
+    let uri = uri(file).ok_or("Failed to create the file URI")?;
+    let metadata = std::fs::metadata(file)?;
+    let modified = modified(file).ok_or("Failed to read the modification time")?;
+
+    // the spec asks for a cache that only the user can read
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(directory)?;
+
+    let path = directory.join(file_name(&uri));
+    let temporary = path.with_extension(format!("ponyview-{}", std::process::id()));
+
+    let image = image.to_rgba8();
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&temporary)?), image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder.add_text_chunk("Thumb::URI".to_string(), uri)?;
+    encoder.add_text_chunk("Thumb::MTime".to_string(), modified.to_string())?;
+    encoder.add_text_chunk("Thumb::Size".to_string(), metadata.len().to_string())?;
+    encoder.add_text_chunk("Software".to_string(), "ponyview".to_string())?;
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+
+    // other readers must never see a half written thumbnail, so it is moved into place at the end
+    std::fs::set_permissions(&temporary, std::fs::Permissions::from_mode(0o600))?;
+    std::fs::rename(&temporary, &path)?;
+
+    Ok(())
+}
@@ -0,0 +1,399 @@
+mod encoder;
+mod freedesktop;
+
+use crate::config;
+use crate::format;
+use crate::paintable::{animation, Decoded, Orientation, SmartPaintable};
+use crate::pool::Pool;
+
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::PathBuf,
+    sync::{atomic::{AtomicU32, Ordering}, Mutex},
+    time::{SystemTime, UNIX_EPOCH}
+};
+use image::{DynamicImage, ImageReader};
+use serde::Deserialize;
+
+pub use encoder::ThumbnailFormat;
+
+
+/// The size thumbnails are generated at right now, 0 until it is changed from the configured one
+static SIZE: AtomicU32 = AtomicU32::new(0);
+/// Bytes written to the cache since its size was last checked
+static WRITTEN: Mutex<u64> = Mutex::new(0);
+/// Going through the whole cache is slow, so its size is checked whenever this part of the limit was written
+const LIMIT_CHECK_FRACTION: u64 = 10;
+
+const THUMBNAIL_ANIMATIONS: &[(&str, ThumbnailAnimation)] = &[
+    ("all", ThumbnailAnimation::All),
+    ("highlighted", ThumbnailAnimation::Highlighted),
+    ("none", ThumbnailAnimation::Disabled)
+];
+
+/// Which animated thumbnails are played in thumbnail mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ThumbnailAnimation {
+    All,
+    #[default]
+    Highlighted,
+    /// Only show the first frame
+    Disabled
+}
+
+impl std::str::FromStr for ThumbnailAnimation {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        THUMBNAIL_ANIMATIONS.iter()
+            .find(|(animation, _)| *animation == name)
+            .map(|(_, animation)| *animation)
+            .ok_or_else(|| format!("unknown thumbnail animation `{name}`"))
+    }
+}
+
+impl TryFrom<String> for ThumbnailAnimation {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+/// Extension of the files next to each thumbnail that describe the file it was made from
+const FINGERPRINT_EXTENSION: &str = "meta";
+
+
+fn cache_path() -> std::path::PathBuf {
+    let path = 'path: {
+        let path = std::env::var("XDG_CACHE_HOME");
+        if path.is_ok() { break 'path std::path::PathBuf::from(&path.unwrap()) }
+
+        let path = std::env::home_dir().unwrap().join(".cache");
+        path
+    }.join("ponyview");
+
+    std::fs::create_dir_all(path.clone());
+    return path
+}
+
+fn find_file_without_extension(file: &std::path::Path) -> Option<std::path::PathBuf> {
+    let file_name = file.file_name()?;
+
+    for child in file.parent()?.read_dir().ok()? {
+        let Ok(binding) = child else { continue };
+        let path = binding.path();
+        if path.extension().is_some_and(|e| e == FINGERPRINT_EXTENSION) { continue }
+
+        // compare the whole stem, the entry of another size starts with the same hash
+        if path.file_stem() == Some(file_name) {
+            return Some(path)
+        }
+    }
+
+    return None
+}
+
+fn path_hash(file: &std::path::Path) -> String {
+    blake3::hash(file.as_os_str().as_encoded_bytes())
+        .to_hex()
+        .to_lowercase()
+}
+
+/// Every size gets its own cache entry, so switching back and forth doesn't regenerate them
+fn cache_stem(file: &std::path::Path, size: u32) -> String {
+    format!("{}-{size}", path_hash(file))
+}
+
+pub fn size() -> u32 {
+    match SIZE.load(Ordering::Relaxed) {
+        0 => config::get().settings.thumbnail_size,
+        size => size
+    }
+}
+
+pub fn set_size(size: u32) {
+    SIZE.store(size, Ordering::Relaxed);
+}
+
+/// The configured thumbnail sizes in ascending order
+pub fn sizes() -> Vec<u32> {
+    let mut sizes = config::get().settings.thumbnail_sizes.clone();
+    sizes.retain(|size| *size > 0);
+    sizes.sort();
+    sizes.dedup();
+    sizes
+}
+
+/// Describes the state of a file when its thumbnail is made, once it differs the thumbnail is outdated
+fn fingerprint(file: &std::path::Path) -> Option<String> {
+    let metadata = std::fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let mut fingerprint = format!("path {}\nmodified {}.{:09}\nsize {}\n",
+        file.display(),
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        metadata.len());
+
+    // catches edits that keep the modification time, but the whole file has to be read for it
+    if config::get().settings.thumbnail_content_hash {
+        let hash = blake3::hash(&std::fs::read(file).ok()?);
+        fingerprint.push_str(&format!("hash {}\n", hash.to_hex()));
+    }
+
+    Some(fingerprint)
+}
+
+pub fn to_cache(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    to_cache_with_size(file, size())
+}
+
+fn to_cache_with_size(file: &std::path::Path, size: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let result = write_cache(file, size);
+
+    // let other applications know that they don't have to try either
+    if result.is_err() && config::get().settings.freedesktop_thumbnails {
+        let _ = freedesktop::save_failure(file);
+    }
+
+    result
+}
+
+fn write_cache(file: &std::path::Path, size: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let extension = config::get().settings.thumbnail_format.extension();
+
+    let fingerprint = fingerprint(file).ok_or("Failed to read the file metadata")?;
+    let bytes = std::fs::read(file)?;
+    let input_format = format::detect_file(&bytes, file)
+        .ok_or("Failed to detect the image format")?;
+
+    let binding = cache_path().join(cache_stem(file, size));
+    let mut cache_file = binding.as_path().with_extension(extension);
+
+    if let Some(frames) = animation::frames(&bytes, input_format)? {
+        let frames = frames.collect_frames()?;
+        if frames.is_empty() { return Err("Animation has no frames".into()) }
+
+        let frames = frames.into_iter()
+            .map(|frame| {
+                let delay = frame.delay();
+                let buffer = DynamicImage::from(frame.into_buffer())
+                    .resize(size, size, image::imageops::FilterType::Gaussian)
+                    .into_rgba8();
+
+                image::Frame::from_parts(buffer, 0, 0, delay)
+            })
+            .collect::<Vec<image::Frame>>();
+
+        // the shared cache only knows still thumbnails
+        let settings = &config::get().settings;
+        if settings.freedesktop_thumbnails {
+            let first = DynamicImage::from(frames[0].buffer().clone());
+            return freedesktop::save(file, &first, size)
+        }
+
+        cache_file = binding.with_extension(encoder::ANIMATION_EXTENSION);
+        while let Some(previous) = find_file_without_extension(&binding) { std::fs::remove_file(previous)? }
+        encoder::save_animation(frames, &cache_file)?;
+    } else {
+        let mut reader = ImageReader::new(Cursor::new(&bytes));
+        reader.set_format(input_format);
+
+        let image = reader.decode()?;
+
+        let image = match Orientation::from_exif(&bytes) {
+            Some(orientation) if !config::get().options.no_auto_orient => orientation.apply(image),
+            _ => image
+        };
+
+        // keeps the aspect ratio, so the longest edge becomes `size`
+        let image = image.resize(size, size, image::imageops::FilterType::Gaussian);
+
+        let settings = &config::get().settings;
+        if settings.freedesktop_thumbnails { return freedesktop::save(file, &image, size) }
+
+        // a thumbnail in another format would otherwise be found instead of the new one
+        while let Some(previous) = find_file_without_extension(&binding) { std::fs::remove_file(previous)? }
+        encoder::save(&image, &cache_file)?;
+    }
+
+    std::fs::write(cache_file.with_extension(FINGERPRINT_EXTENSION), fingerprint)?;
+    note_written(std::fs::metadata(&cache_file).map(|metadata| metadata.len()).unwrap_or(0));
+
+    Ok(())
+}
+
+/// Keeps the cache within its limit while thumbnails are being generated
+fn note_written(bytes: u64) {
+    let megabytes = config::get().settings.thumbnail_cache_size;
+    if megabytes == 0 { return }
+
+    // only one worker checks at a time
+    let mut written = WRITTEN.lock().unwrap();
+    *written += bytes;
+    if *written < megabytes * 1024 * 1024 / LIMIT_CHECK_FRACTION { return }
+
+    *written = 0;
+    enforce_cache_limit();
+}
+
+fn find_cached(file: &std::path::Path, size: u32) -> Option<std::path::PathBuf> {
+    if config::get().settings.freedesktop_thumbnails { return freedesktop::find(file, size) }
+
+    let cache_file = find_file_without_extension(&cache_path().join(cache_stem(file, size)))?;
+    if !cache_file.exists() { return None }
+
+    // the file changed since the thumbnail was made
+    let fingerprint_file = cache_file.with_extension(FINGERPRINT_EXTENSION);
+    let cached_fingerprint = std::fs::read_to_string(&fingerprint_file).ok()?;
+    if Some(cached_fingerprint) != fingerprint(file) { return None }
+
+    // the modification time of the fingerprint tells when a thumbnail was used last
+    let _ = std::fs::File::options()
+        .write(true)
+        .open(&fingerprint_file)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+
+    Some(cache_file)
+}
+
+pub fn from_cache(file: &std::path::Path) -> Option<SmartPaintable> {
+    let cache_file = find_cached(file, size())?;
+
+    let paintable = SmartPaintable::default();
+    paintable.load_from_file(&cache_file).ok()?;
+    Some(paintable)
+}
+
+/// Loads the thumbnail of `file`, generating it first if it isn't cached yet
+pub fn load(file: &std::path::Path) -> Result<SmartPaintable, Box<dyn std::error::Error>> {
+    let paintable = SmartPaintable::default();
+    paintable.load_decoded(decode(file, size())?);
+    Ok(paintable)
+}
+
+/// A thumbnail together with the size it was made at
+pub type Thumbnail = (u32, Result<Decoded, String>);
+
+/// Starts the workers that generate thumbnails in the background
+pub fn pool(threads: usize) -> Pool<Thumbnail> {
+    Pool::new(threads, |file| {
+        // the size can change while we work, so the result says which one it is for
+        let size = size();
+        (size, decode(file, size).map_err(|error| error.to_string()))
+    })
+}
+
+/// Like `load` with a thumbnail of `size`, but safe to call from any thread
+pub fn decode(file: &std::path::Path, size: u32) -> Result<Decoded, Box<dyn std::error::Error>> {
+    // a cached thumbnail that can't be read is generated again
+    if let Some(decoded) = find_cached(file, size).and_then(|cache_file| Decoded::from_file(&cache_file).ok()) {
+        return Ok(decoded)
+    }
+
+    if config::get().settings.freedesktop_thumbnails && freedesktop::has_failed(file) {
+        return Err("Creating a thumbnail failed before".into())
+    }
+
+    to_cache_with_size(file, size)?;
+    let cache_file = find_cached(file, size).ok_or("Failed to cache thumbnail")?;
+    Decoded::from_file(&cache_file)
+}
+
+/// A thumbnail in the cache together with its fingerprint
+struct CacheEntry {
+    files: Vec<PathBuf>,
+    bytes: u64,
+    last_used: SystemTime,
+    /// The file the thumbnail was made from
+    source: Option<PathBuf>
+}
+
+impl CacheEntry {
+    fn remove(&self) -> std::io::Result<()> {
+        for file in self.files.iter() { std::fs::remove_file(file)? }
+        Ok(())
+    }
+}
+
+fn cache_entries() -> Vec<CacheEntry> {
+    let mut entries: HashMap<std::ffi::OsString, CacheEntry> = HashMap::new();
+    let Ok(children) = cache_path().read_dir() else { return Vec::new() };
+
+    for child in children.filter_map(|c| c.ok()) {
+        let (path, Ok(metadata)) = (child.path(), child.metadata()) else { continue };
+        let Some(stem) = path.file_stem().map(|s| s.to_os_string()) else { continue };
+
+        let entry = entries.entry(stem).or_insert(CacheEntry {
+            files: Vec::new(),
+            bytes: 0,
+            last_used: UNIX_EPOCH,
+            source: None
+        });
+
+        if path.extension().is_some_and(|e| e == FINGERPRINT_EXTENSION) {
+            entry.source = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| text.lines().find_map(|l| l.strip_prefix("path ").map(PathBuf::from)));
+        }
+
+        entry.last_used = entry.last_used.max(metadata.modified().unwrap_or(UNIX_EPOCH));
+        entry.bytes += metadata.len();
+        entry.files.push(path);
+    }
+
+    entries.into_values().collect()
+}
+
+/// How many thumbnails are cached and how many bytes they take up
+pub fn cache_stats() -> (usize, u64) {
+    let entries = cache_entries();
+    (entries.len(), entries.iter().map(|e| e.bytes).sum())
+}
+
+pub fn clear_cache() -> Result<(), Box<dyn std::error::Error>> {
+    for entry in cache_entries() { entry.remove()? }
+    Ok(())
+}
+
+/// Removes the thumbnails of files that don't exist anymore, returns how many were removed
+pub fn prune_cache() -> Result<usize, Box<dyn std::error::Error>> {
+    let mut count = 0;
+    for entry in cache_entries() {
+        if entry.source.as_ref().is_some_and(|source| source.exists()) { continue }
+
+        entry.remove()?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Keeps the cache within the configured size, called when quitting
+pub fn enforce_cache_limit() {
+    let megabytes = config::get().settings.thumbnail_cache_size;
+    if megabytes == 0 { return }
+
+    if let Err(error) = limit_cache(megabytes * 1024 * 1024) {
+        eprintln!("ponyview: failed to limit the thumbnail cache: {error}");
+    }
+}
+
+/// Removes the least recently used thumbnails until the cache is at most `max_bytes` large
+pub fn limit_cache(max_bytes: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = cache_entries();
+    let mut bytes: u64 = entries.iter().map(|e| e.bytes).sum();
+
+    entries.sort_by_key(|e| e.last_used);
+    for entry in entries {
+        if bytes <= max_bytes { break }
+
+        entry.remove()?;
+        bytes -= entry.bytes;
+    }
+
+    Ok(())
+}
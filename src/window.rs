@@ -1,66 +1,163 @@
+use crate::actions::Action;
+use crate::cmdbar::{self, Command};
+use crate::config;
+use crate::filter;
 use crate::constants::*;
-use crate::paintable::SmartPaintable;
+use crate::image_cache::ImageCache;
+use crate::metadata;
+use crate::paintable::{self, Decoded, SmartPaintable};
+use crate::pool::Pool;
+use crate::sort::{self, SortKey};
+use crate::thumbnails::{self, ThumbnailAnimation};
+use crate::zoom::{self, ZoomMode};
 
-use std::{cell::{Cell, OnceCell, RefCell}, path::PathBuf, rc::Rc, sync::Mutex};
+use std::{cell::{Cell, OnceCell, RefCell}, ops::Range, path::{Path, PathBuf}, rc::Rc, sync::{mpsc, Mutex}, time::{Duration, Instant}};
 
-use gtk::{prelude::*, Adjustment};
+use gtk::{gdk, glib, pango, prelude::*, Adjustment};
 
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pan {
+    Step,
+    Page,
+    Edge
+}
+
+/// A file with its thumbnail and the child of the grid showing it
+type Entry = (PathBuf, SmartPaintable, gtk::FlowBoxChild);
+
+/// The file list as it was before a filter hid part of it
+struct Stash {
+    entries: Vec<Entry>,
+    /// How the entries were sorted
+    sort: (SortKey, bool)
+}
+
+/// A sort that is worked out on another thread
+struct PendingSort {
+    /// The order only fits the files it was worked out for
+    files: Vec<PathBuf>,
+    receiver: mpsc::Receiver<Vec<usize>>,
+    /// Whether to start at the first file once sorted, for the files the window was opened with
+    is_initial: bool
+}
+
 pub struct Window {
-    pub zoom: Cell<usize>,
+    pub zoom: Cell<f64>,
+    pub zoom_mode: Cell<ZoomMode>,
     pub cursor: Cell<usize>,
-    pub rotation: Cell<i32>,
     pub is_thumbnail_mode: Cell<bool>,
+    pub sort_key: Cell<SortKey>,
+    pub is_sort_reversed: Cell<bool>,
+    filter_stash: RefCell<Option<Stash>>,
+    pending_sort: RefCell<Option<PendingSort>>,
 
     pub files: Rc<Mutex<Vec<PathBuf>>>,
     pub thumbnails: Rc<Mutex<Vec<SmartPaintable>>>,
 
     pub focused_image: RefCell<SmartPaintable>,
+    image_cache: RefCell<ImageCache>,
+    prefetch_pool: OnceCell<Pool<Result<Decoded, String>>>,
+    /// Sends files with the stem to export their frames under to a thread that does so
+    /// without holding up the interface, and receives how many frames were written
+    exports: OnceCell<(mpsc::Sender<(PathBuf, String)>, mpsc::Receiver<(PathBuf, Result<usize, String>)>)>,
+    /// The image that was focused before, to tell in which direction we are going
+    previous_focused_image: Cell<usize>,
+    /// The frame of the focused image the bar shows, so it is only updated when that changes
+    bar_frame: Cell<usize>,
+    /// When the slideshow got to the current image, `None` while there is no slideshow
+    slideshow_shown_at: Cell<Option<Instant>>,
+    slideshow_interval: Cell<Duration>,
+    /// Files a shuffled slideshow hasn't shown yet, the last one comes next
+    slideshow_queue: RefCell<Vec<PathBuf>>,
+    /// Files that couldn't be loaded and why
+    failures: RefCell<Vec<(PathBuf, String)>>,
 
     pub gtk_window: OnceCell<gtk::ApplicationWindow>,
 
     pub focused_view: OnceCell<gtk::Box>,
+    pub image_scroller: OnceCell<gtk::ScrolledWindow>,
     pub image: OnceCell<gtk::Picture>,
+    viewport_size: Cell<(i32, i32)>,
+
+    info_panel: OnceCell<gtk::ScrolledWindow>,
+    info_list: OnceCell<gtk::ListBox>,
+    info_entries: RefCell<Vec<(String, String)>>,
+    /// Scroll position, relative to the scrollable range, to keep when the image is resized
+    pan_anchor: Cell<Option<(f64, f64)>>,
+    drag_start: Cell<(f64, f64)>,
 
     pub bar: OnceCell<gtk::Box>,
     bar_text_left: OnceCell<gtk::Label>,
     bar_text_right: OnceCell<gtk::Label>,
+    command_entry: OnceCell<gtk::Entry>,
+    command_history: RefCell<cmdbar::History>,
+    bar_was_hidden: Cell<bool>,
 
     pub thumbnail_view: OnceCell<gtk::Box>,
     pub thumbnail_scroller: OnceCell<gtk::ScrolledWindow>,
     pub thumbnail_grid: OnceCell<gtk::FlowBox>,
+    thumbnail_pool: OnceCell<Pool<thumbnails::Thumbnail>>,
+    /// Scroll to the highlighted thumbnail once the grid is laid out again
+    is_scroll_pending: Cell<bool>,
     previous_highlighted_image: Cell<usize>
 }
 
 impl Window {
     pub fn new() -> Self {
         Self {
-            zoom: Cell::new(100),
+            zoom: Cell::new(1.0),
+            zoom_mode: Cell::new(ZoomMode::default()),
             cursor: Cell::new(1),
-            rotation: Cell::new(0),
             is_thumbnail_mode: Cell::new(false),
+            sort_key: Cell::new(SortKey::default()),
+            is_sort_reversed: Cell::new(false),
+            filter_stash: RefCell::new(None),
+            pending_sort: RefCell::new(None),
 
             files: Rc::new(Mutex::new(Vec::new())),
             thumbnails: Rc::new(Mutex::new(Vec::new())),
             focused_image: RefCell::new(SmartPaintable::default()),
+            image_cache: RefCell::new(ImageCache::new(0)),
+            prefetch_pool: OnceCell::new(),
+            exports: OnceCell::new(),
+            previous_focused_image: Cell::new(1),
+            bar_frame: Cell::new(0),
+            slideshow_shown_at: Cell::new(None),
+            slideshow_interval: Cell::new(Duration::ZERO),
+            slideshow_queue: RefCell::new(Vec::new()),
+            failures: RefCell::new(Vec::new()),
 
             gtk_window: OnceCell::new(),
 
             focused_view: OnceCell::new(),
+            image_scroller: OnceCell::new(),
             image: OnceCell::new(),
+            viewport_size: Cell::new((0, 0)),
+
+            info_panel: OnceCell::new(),
+            info_list: OnceCell::new(),
+            info_entries: RefCell::new(Vec::new()),
+            pan_anchor: Cell::new(None),
+            drag_start: Cell::new((0.0, 0.0)),
 
             bar: OnceCell::new(),
             bar_text_left: OnceCell::new(),
             bar_text_right: OnceCell::new(),
+            command_entry: OnceCell::new(),
+            command_history: RefCell::new(cmdbar::History::default()),
+            bar_was_hidden: Cell::new(false),
 
             thumbnail_view: OnceCell::new(),
             thumbnail_scroller: OnceCell::new(),
             thumbnail_grid: OnceCell::new(),
+            thumbnail_pool: OnceCell::new(),
+            is_scroll_pending: Cell::new(false),
             previous_highlighted_image: Cell::new(1)
         }
     }
 
-    pub fn construct(&self) {
+    pub fn construct(&'static self) {
        let content = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
@@ -72,21 +169,82 @@ impl Window {
             .height_request(WINDOW_HEIGHT as i32)
             .build()).unwrap();
 
+        // the picture is always shown at the size of the zoomed paintable,
+        // the scroller around it takes care of everything that doesn't fit
         self.image.set(gtk::Picture::builder()
+            .halign(gtk::Align::Center)
+            .valign(gtk::Align::Center)
+            .can_shrink(false)
+            .build()).unwrap();
+
+        self.image_scroller.set(gtk::ScrolledWindow::builder()
             .hexpand(true)
             .vexpand(true)
-            .halign(gtk::Align::Fill)
-            .valign(gtk::Align::Fill)
-            .can_shrink(true)
+            .hscrollbar_policy(gtk::PolicyType::External)
+            .vscrollbar_policy(gtk::PolicyType::External)
+            .child(self.image.get().unwrap())
             .build()).unwrap();
 
+        let scroller = self.image_scroller.get().unwrap();
+        scroller.hadjustment().connect_changed(move |adjustment| {
+            self.viewport_changed();
+            if let Some((x, _)) = self.pan_anchor.get() { set_relative_position(adjustment, x) }
+        });
+        scroller.vadjustment().connect_changed(move |adjustment| {
+            self.viewport_changed();
+            if let Some((_, y)) = self.pan_anchor.get() { set_relative_position(adjustment, y) }
+        });
+
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(move |_, _, _| {
+            let scroller = self.image_scroller.get().unwrap();
+            self.pan_anchor.set(None);
+            self.drag_start.set((scroller.hadjustment().value(), scroller.vadjustment().value()));
+            scroller.set_cursor_from_name(Some("grabbing"));
+        });
+        drag.connect_drag_update(move |_, offset_x, offset_y| {
+            let scroller = self.image_scroller.get().unwrap();
+            let (x, y) = self.drag_start.get();
+            scroller.hadjustment().set_value(x - offset_x);
+            scroller.vadjustment().set_value(y - offset_y);
+        });
+        drag.connect_drag_end(move |_, _, _| {
+            self.image_scroller.get().unwrap().set_cursor_from_name(None);
+        });
+        scroller.add_controller(drag);
+
+        self.info_list.set(gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .activate_on_single_click(true)
+            .can_focus(false)
+            .build()).unwrap();
+
+        // clicking an entry copies its value
+        self.info_list.get().unwrap().connect_row_activated(move |_, row| {
+            let entries = self.info_entries.borrow();
+            if let Some((name, value)) = entries.get(row.index() as usize) {
+                row.clipboard().set_text(value);
+                self.show_message(&format!("copied {name}"));
+            }
+        });
+
+        self.info_panel.set(gtk::ScrolledWindow::builder()
+            .width_request(300)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vscrollbar_policy(gtk::PolicyType::Automatic)
+            .visible(false)
+            .child(self.info_list.get().unwrap())
+            .build()).unwrap();
+        self.info_panel.get().unwrap().add_css_class("info-panel");
+
         self.focused_view.set(gtk::Box::builder()
-            .orientation(gtk::Orientation::Vertical)
-            .halign(gtk::Align::Center)
-            .valign(gtk::Align::Center)
+            .orientation(gtk::Orientation::Horizontal)
+            .hexpand(true)
+            .vexpand(true)
             .visible(!self.is_thumbnail_mode.get())
             .build()).unwrap();
-        self.focused_view.get().unwrap().append(self.image.get().unwrap());
+        self.focused_view.get().unwrap().append(self.image_scroller.get().unwrap());
+        self.focused_view.get().unwrap().append(self.info_panel.get().unwrap());
 
         self.bar.set(gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
@@ -94,6 +252,7 @@ impl Window {
             .build()).unwrap();
 
         self.bar_text_left.set(gtk::Label::builder()
+            .ellipsize(pango::EllipsizeMode::End)
             .build()).unwrap();
 
         self.bar_text_right.set(gtk::Label::builder()
@@ -101,7 +260,15 @@ impl Window {
             .hexpand(true)
             .build()).unwrap();
 
+        self.command_entry.set(gtk::Entry::builder()
+            .hexpand(true)
+            .has_frame(false)
+            .visible(false)
+            .build()).unwrap();
+        self.connect_command_entry();
+
         self.bar.get().unwrap().add_css_class("bar");
+        self.bar.get().unwrap().append(self.command_entry.get().unwrap());
         self.bar.get().unwrap().append(self.bar_text_left.get().unwrap());
         self.bar.get().unwrap().append(self.bar_text_right.get().unwrap());
 
@@ -131,28 +298,169 @@ impl Window {
 
         self.thumbnail_view.get().unwrap().append(self.thumbnail_scroller.get().unwrap());
 
+        // thumbnails are generated in the background and shown as they finish,
+        // the ones that can be seen are generated first
+        self.thumbnail_pool.set(thumbnails::pool(config::get().settings.thumbnail_threads)).unwrap();
+
+        // the images next to the focused one are decoded ahead of time
+        let settings = &config::get().settings;
+        self.image_cache.replace(ImageCache::new(settings.image_cache_size as usize * 1024 * 1024));
+        self.prefetch_pool.set(Pool::new(PREFETCH_THREADS, |file| {
+            Decoded::from_file_streamed(file).map_err(|error| error.to_string())
+        })).unwrap();
+        let (sender, receiver) = mpsc::channel::<(PathBuf, String)>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (file, stem) in receiver {
+                let result = paintable::export_frames(&file, &config::get().settings.export_directory, &stem);
+                if result_sender.send((file, result.map_err(|error| error.to_string()))).is_err() { return }
+            }
+        });
+        self.exports.set((sender, result_receiver)).unwrap();
+        self.slideshow_interval.set(Duration::from_secs_f64(settings.slideshow_interval));
+
+        glib::timeout_add_local(Duration::from_millis(50), move || {
+            self.receive_thumbnails();
+            self.receive_prefetched();
+            self.receive_exports();
+            self.receive_sorted();
+            self.refresh_frame_counter();
+            self.slideshow_tick();
+            glib::ControlFlow::Continue
+        });
+
+        self.thumbnail_scroller.get().unwrap().vadjustment().connect_value_changed(move |_| {
+            self.prioritize_thumbnails();
+        });
+        self.thumbnail_scroller.get().unwrap().vadjustment().connect_changed(move |_| {
+            if self.is_scroll_pending.take() { glib::idle_add_local_once(move || self.cursor_changed()); }
+        });
+
         content.append(self.bar.get().unwrap());
         content.append(self.focused_view.get().unwrap());
         content.append(self.thumbnail_view.get().unwrap());
     }
 
-    pub fn marked_files(&self) -> Vec<usize> {
-        let guard = self.files.lock().unwrap();
-        let file_count = guard.len();
-        let mut marked_files: Vec<usize> = Vec::new();
+    fn connect_command_entry(&'static self) {
+        let entry = self.command_entry.get().unwrap();
+        entry.add_css_class("command-bar");
+
+        entry.connect_activate(move |entry| {
+            let text = entry.text();
+            let line = text.strip_prefix(':').unwrap_or(&text);
+            self.close_command_bar();
+
+            // recalled lines get their colon back
+            self.command_history.borrow_mut().push(line);
+            self.run_command_line(line);
+        });
+
+        let controller = gtk::EventControllerKey::new();
+        controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        controller.connect_key_pressed(move |_, key, _, _| {
+            let line = match key {
+                gdk::Key::Escape => {
+                    self.close_command_bar();
+                    return glib::Propagation::Stop
+                }
+
+                gdk::Key::Up => self.command_history.borrow_mut().previous().map(str::to_string),
+                gdk::Key::Down => self.command_history.borrow_mut().next().map(str::to_string),
+                _ => return glib::Propagation::Proceed
+            };
+
+            if let Some(line) = line {
+                let entry = self.command_entry.get().unwrap();
+                entry.set_text(&format!(":{line}"));
+                entry.set_position(-1);
+            }
 
-        for i in 0 .. file_count - 1 {
-            let child = self.thumbnail_grid.get().unwrap().child_at_index((i) as i32)
-                .unwrap()
-                .child()
-                .unwrap();
+            glib::Propagation::Stop
+        });
+
+        entry.add_controller(controller);
+    }
+
+    pub fn is_command_bar_open(&self) -> bool {
+        self.command_entry.get().unwrap().is_visible()
+    }
+
+    pub fn open_command_bar(&self) {
+        let bar = self.bar.get().unwrap();
+        self.bar_was_hidden.set(!bar.is_visible());
+        bar.set_visible(true);
+
+        self.bar_text_left.get().unwrap().set_visible(false);
+        self.bar_text_right.get().unwrap().set_visible(false);
+
+        let entry = self.command_entry.get().unwrap();
+        entry.set_visible(true);
+        entry.set_text(":");
+        entry.grab_focus();
+        entry.set_position(-1);
+    }
+
+    pub fn close_command_bar(&self) {
+        self.command_history.borrow_mut().reset();
+        self.command_entry.get().unwrap().set_visible(false);
+
+        self.bar_text_left.get().unwrap().set_visible(true);
+        self.bar_text_right.get().unwrap().set_visible(true);
+        if self.bar_was_hidden.get() { self.bar.get().unwrap().set_visible(false) }
+
+        self.gtk_window.get().unwrap().set_focus(None::<&gtk::Widget>);
+    }
 
-            if child.has_css_class("is-marked") { marked_files.push(i + 1) }
+    pub fn run_command_line(&self, line: &str) {
+        match line.parse::<Command>() {
+            Ok(command) => self.run_command(command),
+            Err(error) => self.show_message(&error)
         }
+    }
 
-        std::mem::drop(guard);
+    pub fn run_command(&self, command: Command) {
+        match command {
+            Command::Action(action) => { self.run_action(action) }
+
+            Command::Goto(index) => {
+                let length = self.files.lock().unwrap().len();
+                if index < 1 || index > length {
+                    self.show_message(&format!("goto: no image {index}"));
+                    return
+                }
+
+                self.cursor.set(index);
+                self.cursor_changed();
+            }
+
+            Command::Zoom(zoom) => { self.set_zoom(zoom as f64 / 100.0) }
+            Command::ZoomMode(mode) => { self.set_zoom_mode(mode) }
+
+            Command::Rotate(angle) => { self.rotate_focused_image(angle) }
+
+            Command::Sort(key, is_reversed) => { self.sort_files(key, is_reversed) }
+            Command::Filter(Some(pattern)) => { self.filter_files(&pattern) }
+            Command::Filter(None) => { self.clear_filter() }
+
+            Command::Help => { self.show_message(&cmdbar::help()) }
+        }
+    }
+
+    pub fn show_message(&self, message: &str) {
+        self.bar_text_left.get().unwrap().set_text(&format!("  {message}"));
+    }
 
-        marked_files
+    /// Every marked file, including the ones a filter hides
+    pub fn marked_files(&self) -> Vec<PathBuf> {
+        let entries = match &*self.filter_stash.borrow() {
+            Some(stash) => stash.entries.clone(),
+            None => self.entries()
+        };
+
+        entries.into_iter()
+            .filter(|(_, _, child)| child.child().is_some_and(|child| child.has_css_class("is-marked")))
+            .map(|(file, _, _)| file)
+            .collect()
     }
 
     pub fn is_fullscreen(&self) -> bool {
@@ -161,12 +469,156 @@ impl Window {
 
     pub fn set_focused_image(&self, cursor: usize) {
         let focused_image = self.load_image(cursor);
+        if focused_image.error().is_some() && config::get().options.drop_failed {
+            self.remove_file(cursor);
+            return
+        }
+
+        // a cached image looks the way it did when it was loaded, not the way it was left
+        if focused_image != *self.focused_image.borrow() {
+            // an image that isn't shown anymore doesn't need to keep playing
+            self.focused_image.borrow().pause();
+            focused_image.reset_view();
+        }
 
         self.image.get().unwrap().set_paintable(Some(&focused_image));
         self.cursor.set(cursor);
 
         self.focused_image.replace(focused_image);
-        self.update_bar(&self.focused_image.borrow());
+        self.update_zoom();
+        self.update_info();
+
+        // start at the top of the image, centered horizontally
+        self.pan_anchor.set(Some((0.5, 0.0)));
+        let scroller = self.image_scroller.get().unwrap();
+        set_relative_position(&scroller.hadjustment(), 0.5);
+        set_relative_position(&scroller.vadjustment(), 0.0);
+
+        self.prefetch(cursor);
+        self.previous_focused_image.set(cursor);
+    }
+
+    pub fn toggle_info(&self) {
+        let panel = self.info_panel.get().unwrap();
+        panel.set_visible(!panel.is_visible());
+        self.update_info();
+    }
+
+    /// Fills the info panel with the metadata of the focused image
+    fn update_info(&self) {
+        if !self.info_panel.get().unwrap().is_visible() { return }
+
+        let list = self.info_list.get().unwrap();
+        list.remove_all();
+
+        let guard = self.files.lock().unwrap();
+        let entries = match guard.get(self.cursor.get().wrapping_sub(1)) {
+            Some(file) => metadata::read(file),
+            None => Vec::new()
+        };
+        std::mem::drop(guard);
+
+        for (name, value) in entries.iter() {
+            let row = gtk::Box::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .margin_start(8)
+                .margin_end(8)
+                .margin_top(4)
+                .margin_bottom(4)
+                .build();
+
+            let name = gtk::Label::builder()
+                .label(name.as_str())
+                .xalign(0.0)
+                .build();
+            name.add_css_class("info-name");
+
+            let value = gtk::Label::builder()
+                .label(value.as_str())
+                .xalign(0.0)
+                .wrap(true)
+                .wrap_mode(pango::WrapMode::WordChar)
+                .build();
+
+            row.append(&name);
+            row.append(&value);
+            list.append(&row);
+        }
+
+        self.info_entries.replace(entries);
+    }
+
+    pub fn pan(&self, pan: Pan, x: i32, y: i32) {
+        if self.is_thumbnail_mode.get() { return }
+
+        let scroller = self.image_scroller.get().unwrap();
+        self.pan_anchor.set(None);
+
+        for (adjustment, direction) in [(scroller.hadjustment(), x), (scroller.vadjustment(), y)] {
+            if direction == 0 { continue }
+
+            // the adjustment clamps the value, so the image never leaves the view
+            let value = match pan {
+                Pan::Step => adjustment.value() + adjustment.step_increment() * direction as f64,
+                Pan::Page => adjustment.value() + adjustment.page_increment() * direction as f64,
+                Pan::Edge if direction < 0 => adjustment.lower(),
+                Pan::Edge => adjustment.upper()
+            };
+
+            adjustment.set_value(value);
+        }
+    }
+
+    /// Keeps the current scroll position while the zoom changes
+    fn anchor_pan(&self) {
+        let scroller = self.image_scroller.get().unwrap();
+        self.pan_anchor.set(Some((
+            relative_position(&scroller.hadjustment()),
+            relative_position(&scroller.vadjustment()))));
+    }
+
+    fn viewport_changed(&'static self) {
+        let scroller = self.image_scroller.get().unwrap();
+        let size = (scroller.width(), scroller.height());
+        if size == self.viewport_size.get() { return }
+
+        // the viewport is being allocated right now, resize the image afterwards
+        self.viewport_size.set(size);
+        glib::idle_add_local_once(move || self.update_zoom());
+    }
+
+    /// Applies the zoom mode to the focused image and shows the resulting zoom in the bar
+    pub fn update_zoom(&self) {
+        let paintable = self.focused_image.borrow();
+        let image = (paintable.image_width() as f64, paintable.image_height() as f64);
+        let (width, height) = self.viewport_size.get();
+
+        let is_measurable = image.0 > 0.0 && image.1 > 0.0 && width > 0 && height > 0;
+        if is_measurable || self.zoom_mode.get() == ZoomMode::Manual {
+            let scale = self.zoom_mode.get().scale(self.zoom.get(), image, (width as f64, height as f64));
+            self.zoom.set(scale);
+            paintable.set_scale(scale);
+        }
+
+        std::mem::drop(paintable);
+        self.refresh_bar();
+    }
+
+    pub fn set_zoom(&self, zoom: f64) {
+        if self.is_thumbnail_mode.get() { return }
+
+        self.anchor_pan();
+        self.zoom_mode.set(ZoomMode::Manual);
+        self.zoom.set(zoom::clamp(zoom));
+        self.update_zoom();
+    }
+
+    pub fn set_zoom_mode(&self, mode: ZoomMode) {
+        if self.is_thumbnail_mode.get() { return }
+
+        self.anchor_pan();
+        self.zoom_mode.set(mode);
+        self.update_zoom();
     }
 
     pub fn set_highlighted_image(&self, cursor: usize) {
@@ -179,27 +631,487 @@ impl Window {
 
         let previous_child = self.thumbnail_grid.get().unwrap()
             .child_at_index((self.previous_highlighted_image.get() - 1) as i32)
-            .unwrap()
-            .child()
-            .unwrap();
+            .and_then(|child| child.child());
 
         // TODO: make border not jiggle the listbox
-        previous_child.remove_css_class("highlighted-thumbnail");
+        if let Some(previous_child) = previous_child { previous_child.remove_css_class("highlighted-thumbnail") }
         child.add_css_class("highlighted-thumbnail");
 
         let guard = self.thumbnails.lock().unwrap();
         self.previous_highlighted_image.set(cursor);
         self.update_bar(guard.get(cursor - 1).unwrap());
+        std::mem::drop(guard);
+
+        self.update_thumbnail_animations();
     }
 
     pub fn load_image(&self, cursor: usize) -> SmartPaintable {
-        let guard = self.files.lock().unwrap();
+        let file = self.files.lock().unwrap().get(cursor - 1).unwrap().clone();
+        if let Some(paintable) = self.image_cache.borrow_mut().get(&file) { return paintable }
+
         let paintable = SmartPaintable::default();
+        match Decoded::from_file_streamed(&file) {
+            Ok(decoded) => paintable.load_decoded(decoded),
+            Err(error) => {
+                paintable.set_error(&error.to_string());
+                self.record_failure(&file, &error.to_string());
+                return paintable
+            }
+        }
 
-        paintable.load_from_file(guard.get(cursor - 1).unwrap());
+        self.image_cache.borrow_mut().insert(file, paintable.clone());
         paintable
     }
 
+    /// Decodes the images around `cursor` in the background, the ones in the direction we are going first
+    fn prefetch(&self, cursor: usize) {
+        let count = config::get().settings.prefetch_count;
+        let is_forward = cursor >= self.previous_focused_image.get();
+        let index = cursor - 1;
+
+        let ahead = (1..=count).filter_map(|offset| if is_forward { index.checked_add(offset) } else { index.checked_sub(offset) });
+        let behind = (1..=count).filter_map(|offset| if is_forward { index.checked_sub(offset) } else { index.checked_add(offset) });
+
+        let guard = self.files.lock().unwrap();
+        let cache = self.image_cache.borrow();
+        let files = ahead.chain(behind)
+            .filter_map(|index| guard.get(index))
+            .filter(|file| !cache.contains(file))
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        std::mem::drop(cache);
+        std::mem::drop(guard);
+
+        // whatever was queued for the previous image isn't needed as much anymore
+        let pool = self.prefetch_pool.get().unwrap();
+        pool.clear();
+        for file in files { pool.push(file) }
+    }
+
+    fn receive_prefetched(&self) {
+        for (file, result) in self.prefetch_pool.get().unwrap().finished() {
+            // errors are shown once the image is focused
+            let Ok(decoded) = result else { continue };
+            if !self.files.lock().unwrap().contains(&file) { continue }
+
+            let paintable = SmartPaintable::default();
+            paintable.load_decoded(decoded);
+            self.image_cache.borrow_mut().insert(file, paintable);
+        }
+    }
+
+    /// Loads the focused image from disk again instead of taking it from the cache
+    pub fn reload(&self, cursor: usize) {
+        if self.is_thumbnail_mode.get() { return }
+        let Some(file) = self.files.lock().unwrap().get(cursor.wrapping_sub(1)).cloned() else { return };
+
+        self.image_cache.borrow_mut().remove(&file);
+        self.set_focused_image(cursor);
+    }
+
+    /// Adds a file to the grid with a placeholder, its thumbnail is generated in the background
+    pub fn append_file(&self, file: PathBuf) {
+        let size = thumbnails::size() as i32;
+        let paintable = SmartPaintable::default();
+
+        let frame = gtk::Box::builder()
+            .halign(gtk::Align::Start)
+            .hexpand(true)
+            .width_request(size)
+            .height_request(size)
+            .build();
+        frame.add_css_class("thumbnail-placeholder");
+
+        let thumbnail = gtk::Picture::builder()
+            .paintable(&paintable)
+            .build();
+
+        frame.append(&thumbnail);
+        self.thumbnail_grid.get().unwrap().append(&frame);
+
+        // otherwise the file would be gone once the filter is cleared
+        if let Some(stash) = &mut *self.filter_stash.borrow_mut() {
+            let child = frame.parent().and_then(|parent| parent.downcast::<gtk::FlowBoxChild>().ok()).unwrap();
+            stash.entries.push((file.clone(), paintable.clone(), child));
+        }
+        self.thumbnails.lock().unwrap().push(paintable);
+        self.thumbnail_pool.get().unwrap().push(file.clone());
+        self.files.lock().unwrap().push(file);
+    }
+
+    /// Swaps the placeholders of the thumbnails that finished for the real ones
+    fn receive_thumbnails(&self) {
+        let pool = self.thumbnail_pool.get().unwrap();
+
+        // don't hold up the main loop when a lot of thumbnails finish at once
+        for (file, (size, result)) in pool.finished().take(THUMBNAIL_BATCH_SIZE) {
+            // made before the size changed, it is queued again at the new size
+            if size != thumbnails::size() { continue }
+
+            // the same file can be in the list more than once
+            let guard = self.files.lock().unwrap();
+            let indices = guard.iter()
+                .enumerate()
+                .filter(|(_, f)| **f == file)
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>();
+            std::mem::drop(guard);
+            if indices.is_empty() { continue }
+
+            let paintable = match result {
+                Ok(decoded) => {
+                    let paintable = SmartPaintable::default();
+                    paintable.load_decoded(decoded);
+                    paintable
+                }
+
+                Err(error) => {
+                    self.record_failure(&file, &error);
+                    if config::get().options.drop_failed {
+                        for index in indices.into_iter().rev() { self.remove_file(index + 1) }
+                        continue
+                    }
+
+                    SmartPaintable::from_error(&error)
+                }
+            };
+
+            for index in indices { self.set_thumbnail(index, paintable.clone()) }
+        }
+    }
+
+    fn set_thumbnail(&self, index: usize, paintable: SmartPaintable) {
+        let Some(frame) = self.thumbnail_grid.get().unwrap()
+            .child_at_index(index as i32)
+            .and_then(|child| child.child()) else { return };
+
+        frame.remove_css_class("thumbnail-placeholder");
+        frame.set_size_request(paintable.intrinsic_width(), paintable.intrinsic_height());
+        if let Some(picture) = frame.first_child().and_downcast::<gtk::Picture>() {
+            picture.set_paintable(Some(&paintable));
+        }
+
+        if self.is_thumbnail_animated(index) { paintable.resume() }
+
+        let mut guard = self.thumbnails.lock().unwrap();
+        if let Some(thumbnail) = guard.get_mut(index) { *thumbnail = paintable }
+        std::mem::drop(guard);
+
+        if self.is_thumbnail_mode.get() && self.cursor.get() == index + 1 { self.refresh_bar() }
+    }
+
+    /// Shows a placeholder for a thumbnail and generates it again
+    fn reset_thumbnail(&self, index: usize) {
+        let Some(file) = self.files.lock().unwrap().get(index).cloned() else { return };
+        let size = thumbnails::size() as i32;
+        let paintable = SmartPaintable::default();
+
+        let frame = self.thumbnail_grid.get().unwrap()
+            .child_at_index(index as i32)
+            .and_then(|child| child.child());
+
+        if let Some(frame) = frame {
+            frame.add_css_class("thumbnail-placeholder");
+            frame.set_size_request(size, size);
+            if let Some(picture) = frame.first_child().and_downcast::<gtk::Picture>() {
+                picture.set_paintable(Some(&paintable));
+            }
+        }
+
+        if let Some(thumbnail) = self.thumbnails.lock().unwrap().get_mut(index) { *thumbnail = paintable }
+        self.thumbnail_pool.get().unwrap().push(file);
+    }
+
+    /// Regenerates every thumbnail at `size`, showing placeholders until they are done
+    pub fn set_thumbnail_size(&self, size: u32) {
+        if size == thumbnails::size() { return }
+        thumbnails::set_size(size);
+
+        self.thumbnail_pool.get().unwrap().clear();
+        let length = self.files.lock().unwrap().len();
+        for index in 0..length { self.reset_thumbnail(index) }
+
+        self.is_scroll_pending.set(true);
+        self.prioritize_thumbnails();
+        self.show_message(&format!("thumbnail size {size}"));
+    }
+
+    /// Steps to the next larger or smaller of the configured thumbnail sizes
+    fn step_thumbnail_size(&self, is_larger: bool) {
+        if !self.is_thumbnail_mode.get() { return }
+
+        let (sizes, current) = (thumbnails::sizes(), thumbnails::size());
+        let size = if is_larger {
+            sizes.iter().find(|size| **size > current)
+        } else {
+            sizes.iter().rev().find(|size| **size < current)
+        };
+
+        if let Some(size) = size { self.set_thumbnail_size(*size) }
+    }
+
+    fn is_thumbnail_animated(&self, index: usize) -> bool {
+        if !self.is_thumbnail_mode.get() { return false }
+
+        match config::get().settings.animate_thumbnails {
+            ThumbnailAnimation::All => true,
+            ThumbnailAnimation::Highlighted => index + 1 == self.cursor.get(),
+            ThumbnailAnimation::Disabled => false
+        }
+    }
+
+    /// Plays the animated thumbnails that should be animated and pauses all others
+    fn update_thumbnail_animations(&self) {
+        let guard = self.thumbnails.lock().unwrap();
+        for (index, thumbnail) in guard.iter().enumerate() {
+            let is_animated = self.is_thumbnail_animated(index);
+            if thumbnail.is_playing() == is_animated { continue }
+
+            if is_animated { thumbnail.resume() } else { thumbnail.pause() }
+        }
+    }
+
+    /// Moves the thumbnails that can be seen to the front of the queue
+    fn prioritize_thumbnails(&self) {
+        let range = self.visible_thumbnails();
+        let guard = self.files.lock().unwrap();
+        let files = guard.get(range).unwrap_or_default().to_vec();
+        std::mem::drop(guard);
+
+        self.thumbnail_pool.get().unwrap().prioritize(files);
+    }
+
+    /// The indices of the thumbnails inside the scrolled area, or the ones
+    /// around the cursor while the grid isn't shown
+    fn visible_thumbnails(&self) -> Range<usize> {
+        let length = self.files.lock().unwrap().len();
+        let grid = self.thumbnail_grid.get().unwrap();
+
+        if !grid.is_mapped() {
+            let cursor = self.cursor.get().saturating_sub(1);
+            return cursor.saturating_sub(THUMBNAIL_BATCH_SIZE)..(cursor + THUMBNAIL_BATCH_SIZE).min(length)
+        }
+
+        let adjustment = self.thumbnail_scroller.get().unwrap().vadjustment();
+        let (top, bottom) = (adjustment.value() as i32, (adjustment.value() + adjustment.page_size()) as i32);
+        let bounds = |index: usize| grid.child_at_index(index as i32)
+            .map(|child| child.allocation())
+            .map(|allocation| (allocation.y(), allocation.y() + allocation.height()))
+            .unwrap_or((i32::MAX, i32::MAX));
+
+        // the children are laid out row by row, so their positions only ever grow
+        let first = partition_point(length, |index| bounds(index).1 < top);
+        let last = partition_point(length, |index| bounds(index).0 < bottom);
+        first..last.max(first)
+    }
+
+    pub fn record_failure(&self, file: &Path, error: &str) {
+        let mut failures = self.failures.borrow_mut();
+        if failures.iter().any(|(failed, _)| failed == file) { return }
+
+        failures.push((file.to_path_buf(), error.to_string()));
+    }
+
+    /// Prints the files that couldn't be loaded to stderr, unless we are quiet
+    pub fn print_failures(&self) {
+        let failures = self.failures.borrow();
+        if failures.is_empty() || config::get().options.quiet { return }
+
+        eprintln!("ponyview: failed to load {} file(s):", failures.len());
+        for (file, error) in failures.iter() {
+            eprintln!("  {}: {error}", file.display());
+        }
+    }
+
+    /// Removes a file from the list together with its thumbnail
+    pub fn remove_file(&self, cursor: usize) {
+        let mut guard = self.files.lock().unwrap();
+        if cursor < 1 || cursor > guard.len() { return }
+
+        guard.remove(cursor - 1);
+        let length = guard.len();
+        std::mem::drop(guard);
+
+        let mut guard = self.thumbnails.lock().unwrap();
+        if cursor <= guard.len() { guard.remove(cursor - 1); }
+        std::mem::drop(guard);
+
+        let grid = self.thumbnail_grid.get().unwrap();
+        if let Some(child) = grid.child_at_index((cursor - 1) as i32) {
+            // a removed file doesn't come back when the filter is cleared
+            if let Some(stash) = &mut *self.filter_stash.borrow_mut() { stash.entries.retain(|(_, _, c)| *c != child) }
+            grid.remove(&child);
+        }
+
+        if length == 0 {
+            self.focused_image.replace(SmartPaintable::default());
+            self.image.get().unwrap().set_paintable(None::<&SmartPaintable>);
+            self.bar_text_left.get().unwrap().set_text("  no images");
+            self.bar_text_right.get().unwrap().set_text("");
+            return
+        }
+
+        // removing any other file only shifts the ones after it
+        let current = self.cursor.get();
+        if cursor != current {
+            if cursor < current { self.cursor.set(current - 1) }
+
+            let previous = self.previous_highlighted_image.get();
+            if cursor < previous { self.previous_highlighted_image.set(previous - 1) }
+
+            self.refresh_bar();
+            return
+        }
+
+        self.cursor.set(cursor.min(length));
+        self.previous_highlighted_image.set(self.cursor.get());
+        self.cursor_changed();
+    }
+
+    /// Orders the files by `key`, the current image stays selected and the thumbnails follow along.
+    /// Some keys have to read every file, so the order is worked out on another thread.
+    pub fn sort_files(&self, key: SortKey, is_reversed: bool) {
+        self.sort_key.set(key);
+        self.is_sort_reversed.set(is_reversed);
+        self.start_sort(false);
+    }
+
+    /// Sorts the files the window was opened with by `sort_key`, then shows the first of them
+    /// unless another one was picked in the meantime
+    pub fn sort_opened_files(&self) {
+        if self.sort_key.get() == SortKey::None && !self.is_sort_reversed.get() { return }
+        self.start_sort(true);
+    }
+
+    fn start_sort(&self, is_initial: bool) {
+        let (key, is_reversed) = (self.sort_key.get(), self.is_sort_reversed.get());
+        let files = self.files.lock().unwrap().clone();
+        let (sender, receiver) = mpsc::channel();
+
+        let thread_files = files.clone();
+        std::thread::spawn(move || { let _ = sender.send(sort::order(&thread_files, key, is_reversed)); });
+
+        // a sort that is still running is forgotten
+        self.pending_sort.replace(Some(PendingSort { files, receiver, is_initial }));
+    }
+
+    fn receive_sorted(&self) {
+        let order = self.pending_sort.borrow().as_ref().and_then(|pending| pending.receiver.try_recv().ok());
+        let Some(order) = order else { return };
+        let Some(pending) = self.pending_sort.take() else { return };
+
+        if *self.files.lock().unwrap() != pending.files { return self.start_sort(pending.is_initial) }
+
+        let is_at_start = self.cursor.get() == 1;
+        self.reorder_files(&order);
+
+        if pending.is_initial && is_at_start {
+            self.set_highlighted_image(1);
+            self.cursor_changed();
+        }
+    }
+
+    /// Turns the current order of the files around
+    pub fn reverse_files(&self) {
+        // a sort that is still running finishes in the new direction
+        if self.pending_sort.borrow().is_some() { return self.sort_files(self.sort_key.get(), !self.is_sort_reversed.get()) }
+        self.is_sort_reversed.set(!self.is_sort_reversed.get());
+
+        let order = (0..self.files.lock().unwrap().len()).rev().collect::<Vec<usize>>();
+        self.reorder_files(&order);
+    }
+
+    /// Moves the file at every index of `order` to where that index is
+    fn reorder_files(&self, order: &[usize]) {
+        self.set_entries(sort::apply(&self.entries(), order));
+    }
+
+    /// Hides the files that don't match `pattern` until the filter is cleared,
+    /// a pattern without wildcards matches every name containing it
+    pub fn filter_files(&self, pattern: &str) {
+        let pattern = if filter::Pattern::has_wildcards(pattern) {
+            filter::Pattern::new(pattern)
+        } else {
+            filter::Pattern::new(&format!("*{pattern}*"))
+        };
+
+        let entries = self.entries();
+        let matching = entries.iter()
+            .filter(|(file, _, _)| pattern.matches(file))
+            .cloned()
+            .collect::<Vec<Entry>>();
+        if matching.is_empty() { return self.show_message("filter: no files match") }
+
+        // filtering again narrows the list further, clearing the filter brings all of them back
+        if self.filter_stash.borrow().is_none() {
+            let sort = (self.sort_key.get(), self.is_sort_reversed.get());
+            self.filter_stash.replace(Some(Stash { entries, sort }));
+        }
+
+        self.set_entries(matching);
+    }
+
+    /// Brings back the files the filter hid, sorted the way the list is sorted now
+    pub fn clear_filter(&self) {
+        let Some(stash) = self.filter_stash.take() else { return self.show_message("filter: no filter to clear") };
+        self.set_entries(stash.entries);
+
+        let sort = (self.sort_key.get(), self.is_sort_reversed.get());
+        if sort != stash.sort { self.sort_files(sort.0, sort.1) }
+    }
+
+    fn entries(&self) -> Vec<Entry> {
+        let files = self.files.lock().unwrap().clone();
+        let thumbnails = self.thumbnails.lock().unwrap().clone();
+        let grid = self.thumbnail_grid.get().unwrap();
+
+        files.into_iter()
+            .zip(thumbnails)
+            .enumerate()
+            .filter_map(|(index, (file, thumbnail))| Some((file, thumbnail, grid.child_at_index(index as i32)?)))
+            .collect()
+    }
+
+    /// Replaces the file list with `entries`, the current image stays selected when it is one of them
+    fn set_entries(&self, entries: Vec<Entry>) {
+        if entries.is_empty() { return }
+
+        let grid = self.thumbnail_grid.get().unwrap();
+        let current = grid.child_at_index(self.cursor.get() as i32 - 1);
+        let highlighted = grid.child_at_index(self.previous_highlighted_image.get() as i32 - 1);
+        if let Some(child) = highlighted.and_then(|child| child.child()) { child.remove_css_class("highlighted-thumbnail") }
+
+        let position = entries.iter().position(|(_, _, child)| Some(child) == current.as_ref());
+        let cursor = position.map_or(1, |index| index + 1);
+
+        // the children keep their thumbnail and marks when they are moved
+        let children = self.entries();
+        for (_, _, child) in &children { grid.remove(child) }
+        for (_, _, child) in &entries { grid.append(child) }
+
+        // results for files that were hidden are ignored, so they are queued again
+        let pool = self.thumbnail_pool.get().unwrap();
+        for (file, thumbnail, _) in &entries {
+            if thumbnail.is_empty() { pool.push(file.clone()) }
+        }
+
+        *self.files.lock().unwrap() = entries.iter().map(|(file, _, _)| file.clone()).collect();
+        *self.thumbnails.lock().unwrap() = entries.into_iter().map(|(_, thumbnail, _)| thumbnail).collect();
+
+        self.cursor.set(cursor);
+        self.previous_highlighted_image.set(cursor);
+        self.previous_focused_image.set(cursor);
+        self.set_highlighted_image(cursor);
+
+        if self.is_thumbnail_mode.get() || position.is_none() {
+            self.cursor_changed();
+        } else {
+            self.refresh_bar();
+            self.prefetch(cursor);
+            self.prioritize_thumbnails();
+        }
+    }
+
     pub fn mark_image(&self, cursor: usize) {
         let child = self.thumbnail_grid.get().unwrap().child_at_index((cursor - 1) as i32)
             .unwrap()
@@ -217,8 +1129,43 @@ impl Window {
 
     pub fn rotate_focused_image(&self, angle: i32) {
         if self.is_thumbnail_mode.get() { return }
-        self.rotation.set(self.rotation.get() + angle);
-        self.focused_image.borrow().rotate(self.rotation.get());
+        self.focused_image.borrow().rotate(angle);
+        self.update_zoom();
+    }
+
+    /// Writes the shown frame of the focused image, or all of its frames, to the export directory
+    pub fn export_focused_image(&self, is_all: bool) {
+        if self.is_thumbnail_mode.get() { return }
+
+        let files = self.files.lock().unwrap().clone();
+        let index = self.cursor.get() - 1;
+        let Some(file) = files.get(index).cloned() else { return };
+        let directory = &config::get().settings.export_directory;
+        let stem = paintable::export_stems(&files).swap_remove(index);
+
+        // decoding every frame takes a while, `receive_exports` reports when it is done
+        if is_all {
+            self.show_message(&format!("exporting {stem}"));
+            let _ = self.exports.get().unwrap().0.send((file, stem));
+            return
+        }
+
+        match self.focused_image.borrow().export_frame(directory, &stem) {
+            Ok(count) => self.show_message(&format!("exported {count} frame(s) to {}", directory.display())),
+            Err(error) => self.show_message(&format!("export: {error}"))
+        }
+    }
+
+    fn receive_exports(&self) {
+        let directory = &config::get().settings.export_directory;
+
+        for (file, result) in self.exports.get().unwrap().1.try_iter() {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            match result {
+                Ok(count) => self.show_message(&format!("exported {count} frame(s) of {name} to {}", directory.display())),
+                Err(error) => self.show_message(&format!("export: {name}: {error}"))
+            }
+        }
     }
 
     pub fn flip_focused_image(&self, is_horizontal: bool) {
@@ -232,6 +1179,8 @@ impl Window {
     }
 
     pub fn cursor_changed(&self) {
+        if self.files.lock().unwrap().is_empty() { return }
+
         if self.is_thumbnail_mode.get() {
             self.set_highlighted_image(self.cursor.get());
 
@@ -246,6 +1195,130 @@ impl Window {
         } else {
             self.set_focused_image(self.cursor.get());
         }
+
+        if self.slideshow_shown_at.get().is_some() { self.restart_slideshow_timer() }
+        self.prioritize_thumbnails();
+    }
+
+    pub fn is_slideshow_running(&self) -> bool {
+        self.slideshow_shown_at.get().is_some()
+    }
+
+    /// Starts advancing the cursor on its own every `seconds`
+    pub fn start_slideshow(&self, seconds: f64) {
+        self.slideshow_interval.set(Duration::from_secs_f64(seconds));
+        if config::get().settings.slideshow_shuffle { self.shuffle_slideshow() }
+
+        self.restart_slideshow_timer();
+        self.refresh_bar();
+    }
+
+    pub fn stop_slideshow(&self) {
+        self.slideshow_shown_at.set(None);
+        self.slideshow_queue.borrow_mut().clear();
+        self.refresh_bar();
+    }
+
+    pub fn toggle_slideshow(&self) {
+        if self.is_slideshow_running() {
+            self.stop_slideshow();
+        } else {
+            self.start_slideshow(self.slideshow_interval.get().as_secs_f64());
+        }
+    }
+
+    /// Shows the current image for a whole interval, an animation is played from its first frame
+    fn restart_slideshow_timer(&self) {
+        self.slideshow_shown_at.set(Some(Instant::now()));
+        if self.is_thumbnail_mode.get() || !config::get().settings.slideshow_wait_for_animation { return }
+
+        // focused animations start out paused, the slideshow plays the ones it waits for
+        let paintable = self.focused_image.borrow();
+        if paintable.is_animated() {
+            paintable.resume();
+            paintable.restart();
+        }
+    }
+
+    fn slideshow_tick(&self) {
+        let Some(shown_at) = self.slideshow_shown_at.get() else { return };
+        if shown_at.elapsed() < self.slideshow_interval.get() { return }
+
+        // an animation keeps going until it played through once, unless it was paused or failed to decode
+        if config::get().settings.slideshow_wait_for_animation && !self.is_thumbnail_mode.get() {
+            let paintable = self.focused_image.borrow();
+            if paintable.is_playing() && paintable.loops_played() == 0 { return }
+        }
+
+        let next = if config::get().settings.slideshow_shuffle { self.next_shuffled_file() } else { self.next_file() };
+        match next {
+            Some(cursor) => {
+                self.cursor.set(cursor);
+                self.cursor_changed();
+            }
+
+            None => self.stop_slideshow()
+        }
+    }
+
+    /// The file after the current one, the first one after the last when looping
+    fn next_file(&self) -> Option<usize> {
+        let length = self.files.lock().unwrap().len();
+        let cursor = self.cursor.get();
+
+        if cursor < length { return Some(cursor + 1) }
+        (config::get().settings.slideshow_loop && length > 1).then_some(1)
+    }
+
+    fn next_shuffled_file(&self) -> Option<usize> {
+        if self.slideshow_queue.borrow().is_empty() {
+            if !config::get().settings.slideshow_loop { return None }
+            self.shuffle_slideshow();
+        }
+
+        // files that were removed in the meantime are skipped
+        let guard = self.files.lock().unwrap();
+        let mut queue = self.slideshow_queue.borrow_mut();
+        while let Some(file) = queue.pop() {
+            if let Some(index) = guard.iter().position(|f| *f == file) { return Some(index + 1) }
+        }
+
+        None
+    }
+
+    /// Queues every file except the current one in a random order
+    fn shuffle_slideshow(&self) {
+        let guard = self.files.lock().unwrap();
+        let cursor = self.cursor.get();
+
+        let mut files = guard.iter()
+            .enumerate()
+            .filter(|(index, _)| index + 1 != cursor)
+            .map(|(_, file)| file.clone())
+            .collect::<Vec<PathBuf>>();
+        std::mem::drop(guard);
+
+        sort::shuffle(&mut files);
+        self.slideshow_queue.replace(files);
+    }
+
+    fn refresh_bar(&self) {
+        if self.files.lock().unwrap().is_empty() { return }
+
+        if self.is_thumbnail_mode.get() {
+            let guard = self.thumbnails.lock().unwrap();
+            self.update_bar(guard.get(self.cursor.get() - 1).unwrap());
+        } else {
+            self.update_bar(&self.focused_image.borrow());
+        }
+    }
+
+    /// Keeps the frame shown in the bar up to date while an animation plays
+    fn refresh_frame_counter(&self) {
+        if self.is_thumbnail_mode.get() { return }
+
+        let frame = self.focused_image.borrow().frame_index();
+        if frame != self.bar_frame.get() { self.refresh_bar() }
     }
 
     fn update_bar(&self, image: &SmartPaintable) {
@@ -253,38 +1326,312 @@ impl Window {
         let file_count = guard.len();
 
         let file = guard.get(self.cursor.get() - 1).unwrap();
-        let file_name = file.file_name().unwrap().to_str().unwrap();
-        let metadata = std::fs::File::open(file).unwrap().metadata().unwrap();
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+        let size = std::fs::metadata(file)
+            .map(|metadata| format_byte_unit(metadata.len()))
+            .unwrap_or_else(|_| "?".to_string());
+
+        if let Some(error) = image.error() {
+            self.bar_text_left.get().unwrap().set_text(
+                (format!("  {}\t{}: {}", size, file_name, error)).as_str());
+        } else if image.is_empty() {
+            self.bar_text_left.get().unwrap().set_text(
+                (format!("  {}\t{}", size, file_name)).as_str());
+        } else {
+            self.bar_text_left.get().unwrap().set_text(
+                (format!("  {}\t{}x{}{}\t{}",
+                    size,
+                    image.image_width(),
+                    image.image_height(),
+                    if image.is_auto_oriented() { " (auto-oriented)" } else { "" },
+                    file_name)).as_str());
+        }
 
-        self.bar_text_left.get().unwrap().set_text(
-            (format!("  {}\t{}x{}\t{}",
-                format_byte_unit(metadata.len()),
-                image.intrinsic_width(),
-                image.intrinsic_height(),
-                file_name)).as_str());
+        self.bar_frame.set(image.frame_index());
+        let animation = if image.is_animated() && image.error().is_none() {
+            format!("frame {}/{}  {}x{}{}\t",
+                image.frame_index() + 1,
+                image.frame_count().map(|count| count.to_string()).unwrap_or_else(|| "?".to_string()),
+                image.speed(),
+                if image.is_reversed() { " reversed" } else { "" },
+                if image.is_ping_pong() { " ping-pong" } else { "" })
+        } else {
+            String::new()
+        };
+
+        let slideshow = if self.is_slideshow_running() {
+            format!("slideshow {}s{}\t",
+                self.slideshow_interval.get().as_secs_f64(),
+                if config::get().settings.slideshow_shuffle { " shuffled" } else { "" })
+        } else {
+            String::new()
+        };
+
+        let filtered = match &*self.filter_stash.borrow() {
+            Some(stash) => format!(" of {}", stash.entries.len()),
+            None => String::new()
+        };
 
         self.bar_text_right.get().unwrap().set_text(
-            (format!("{}%\t{}/{}  ",
-                self.zoom.get(),
+            (format!("{}{}{}%\t{}/{}{}  ",
+                slideshow,
+                animation,
+                (self.zoom.get() * 100.0).round(),
                 self.cursor.get(),
-                file_count)).as_str());
+                file_count,
+                filtered)).as_str());
+    }
+
+    pub fn run_action(&self, action: Action) {
+        match action {
+            Action::Quit => { self.quit(config::get().options.stdout) }
+
+            Action::Left => {
+                if self.is_thumbnail_mode.get() {
+                    let cursor = self.cursor.get();
+                    if cursor > 1 { self.set_highlighted_image(cursor - 1) }
+                    self.cursor_changed();
+                } else { self.pan(Pan::Step, -1, 0) }
+            }
+
+            Action::Down => {
+                if self.is_thumbnail_mode.get() {
+                    let binding = self.files.lock().unwrap();
+                    let length = binding.len();
+                    std::mem::drop(binding);
+
+                    let cursor = self.cursor.get();
+                    let stride = flow_box_get_stride(self.thumbnail_grid.get().unwrap(), length);
+
+                    if length >= cursor + stride { self.set_highlighted_image(cursor + stride) }
+                    self.cursor_changed();
+                } else { self.pan(Pan::Step, 0, 1) }
+            }
+
+            Action::Up => {
+                if self.is_thumbnail_mode.get() {
+                    let guard = self.files.lock().unwrap();
+                    let length = guard.len();
+                    std::mem::drop(guard);
+
+                    let cursor = self.cursor.get();
+                    let stride = flow_box_get_stride(self.thumbnail_grid.get().unwrap(), length);
+
+                    if cursor > stride { self.set_highlighted_image(cursor - stride) }
+                    self.cursor_changed();
+                } else { self.pan(Pan::Step, 0, -1) }
+            }
+
+            Action::Right => {
+                if self.is_thumbnail_mode.get() {
+                    let cursor = self.cursor.get();
+                    if self.files.lock().unwrap().len() > cursor {
+                        self.set_highlighted_image(cursor + 1);
+                    }
+                    self.cursor_changed();
+                } else { self.pan(Pan::Step, 1, 0) }
+            }
+
+            Action::PageLeft => { self.pan(Pan::Page, -1, 0) }
+            Action::PageDown => { self.pan(Pan::Page, 0, 1) }
+            Action::PageUp => { self.pan(Pan::Page, 0, -1) }
+            Action::PageRight => { self.pan(Pan::Page, 1, 0) }
+
+            Action::EdgeLeft => { self.pan(Pan::Edge, -1, 0) }
+            Action::EdgeDown => { self.pan(Pan::Edge, 0, 1) }
+            Action::EdgeUp => { self.pan(Pan::Edge, 0, -1) }
+            Action::EdgeRight => { self.pan(Pan::Edge, 1, 0) }
+
+            Action::First => {
+                self.cursor.set(1);
+                self.cursor_changed();
+            }
+
+            Action::Last => {
+                let guard = self.files.lock().unwrap();
+                let length = guard.len();
+                std::mem::drop(guard);
+
+                self.cursor.set(length);
+                self.cursor_changed();
+            }
+
+            Action::Next => {
+                let cursor = self.cursor.get();
+                if self.files.lock().unwrap().len() > cursor {
+                    self.cursor.set(cursor + 1);
+                    self.cursor_changed();
+                }
+            }
+
+            Action::Previous => {
+                let cursor = self.cursor.get();
+                if cursor > 1 {
+                    self.cursor.set(cursor - 1);
+                    self.cursor_changed();
+                }
+            }
+
+            Action::Mark => { self.mark_image(self.cursor.get()) }
+
+            Action::Reload => { self.reload(self.cursor.get()) }
+            Action::ReloadAll => {
+                // TODO: reload all thumbnails in thumbnail mode
+            }
+
+            Action::Remove => { self.remove_file(self.cursor.get()) }
+
+            Action::ToggleFullscreen => { self.toggle_fullscreen() }
+            Action::ToggleBar => {
+                self.bar.get().unwrap().set_visible(!self.bar.get().unwrap().get_visible());
+            }
+
+            Action::ToggleMode => {
+                self.is_thumbnail_mode.set(!self.is_thumbnail_mode.get());
+                self.focused_view.get().unwrap().set_visible(!self.is_thumbnail_mode.get());
+                self.thumbnail_view.get().unwrap().set_visible(self.is_thumbnail_mode.get());
+
+                self.cursor_changed();
+                self.update_thumbnail_animations();
+            }
+
+            Action::ToggleSlideshow => { self.toggle_slideshow() }
+
+            Action::ToggleAnimation => {
+                if !self.is_thumbnail_mode.get() {
+                    let paintable = self.focused_image.borrow();
+                    if paintable.is_playing() { paintable.pause() } else { paintable.resume() }
+                }
+            }
+
+            Action::Antialias => {
+                if !self.is_thumbnail_mode.get() {
+                    let mut font_options = gtk::cairo::FontOptions::new().unwrap();
+                    font_options.set_antialias(gtk::cairo::Antialias::Best);
+                    self.image.get().unwrap().set_font_options(Some(&font_options));
+                }
+            }
+
+            Action::FlipHorizontal => { self.flip_focused_image(true) }
+            Action::FlipVertical => { self.flip_focused_image(false) }
+            Action::RotateLeft => { self.rotate_focused_image(-90) }
+            Action::RotateRight => { self.rotate_focused_image(90) }
+
+            Action::CommandBar => { self.open_command_bar() }
+            Action::ToggleInfo => { self.toggle_info() }
+
+            Action::ZoomIn => { self.set_zoom(zoom::zoom_in(self.zoom.get())) }
+            Action::ZoomOut => { self.set_zoom(zoom::zoom_out(self.zoom.get())) }
+            Action::ZoomActual => { self.set_zoom(1.0) }
+            Action::ZoomFit => { self.set_zoom_mode(ZoomMode::Fit) }
+            Action::ZoomFitWidth => { self.set_zoom_mode(ZoomMode::FitWidth) }
+            Action::ZoomFitHeight => { self.set_zoom_mode(ZoomMode::FitHeight) }
+            Action::ZoomFill => { self.set_zoom_mode(ZoomMode::Fill) }
+            Action::ZoomShrink => { self.set_zoom_mode(ZoomMode::ShrinkOnly) }
+
+            Action::ThumbnailLarger => { self.step_thumbnail_size(true) }
+            Action::ThumbnailSmaller => { self.step_thumbnail_size(false) }
+
+            Action::RestartAnimation
+                | Action::FrameNext
+                | Action::FramePrevious
+                | Action::FrameFirst
+                | Action::FrameLast
+                | Action::SpeedUp
+                | Action::SpeedDown
+                | Action::SpeedReset
+                | Action::ToggleReverse
+                | Action::TogglePingPong => { self.control_animation(action) }
+
+            Action::ExportFrame => { self.export_focused_image(false) }
+            Action::ExportFrames => { self.export_focused_image(true) }
+
+            Action::SortNext => { self.sort_files(self.sort_key.get().next(), self.is_sort_reversed.get()) }
+            Action::SortReverse => { self.reverse_files() }
+            Action::Shuffle => { self.sort_files(SortKey::Shuffle, false) }
+        }
+    }
+
+    /// Runs one of the playback actions on the focused image
+    fn control_animation(&self, action: Action) {
+        if self.is_thumbnail_mode.get() { return }
+
+        let paintable = self.focused_image.borrow();
+        let speed = paintable.speed();
+
+        match action {
+            Action::RestartAnimation => { paintable.restart() }
+            Action::FrameNext => { paintable.step(true) }
+            Action::FramePrevious => { paintable.step(false) }
+            Action::FrameFirst => { paintable.first_frame() }
+            Action::FrameLast => { paintable.last_frame() }
+
+            Action::SpeedUp => {
+                if let Some(speed) = ANIMATION_SPEEDS.iter().find(|s| **s > speed) { paintable.set_speed(*speed) }
+            }
+
+            Action::SpeedDown => {
+                if let Some(speed) = ANIMATION_SPEEDS.iter().rev().find(|s| **s < speed) { paintable.set_speed(*speed) }
+            }
+
+            Action::SpeedReset => { paintable.set_speed(1.0) }
+            Action::ToggleReverse => { paintable.set_reversed(!paintable.is_reversed()) }
+            Action::TogglePingPong => { paintable.set_ping_pong(!paintable.is_ping_pong()) }
+            _ => {}
+        }
+
+        std::mem::drop(paintable);
+        self.refresh_bar();
     }
 
     pub fn quit(&self, stdout: bool) {
         if stdout {
-            let marked_files = self.marked_files();
-
-            let guard = self.files.lock().unwrap();
-            for i in marked_files {
-                let file = guard.get(i).unwrap().to_str().unwrap();
-                println!("{file}");
+            for file in self.marked_files() {
+                println!("{}", file.display());
             }
         }
 
+        self.print_failures();
+        thumbnails::enforce_cache_limit();
         std::process::exit(0);
     }
 }
 
+fn relative_position(adjustment: &gtk::Adjustment) -> f64 {
+    let range = adjustment.upper() - adjustment.page_size() - adjustment.lower();
+    if range <= 0.0 { return 0.5 }
+
+    (adjustment.value() - adjustment.lower()) / range
+}
+
+fn set_relative_position(adjustment: &gtk::Adjustment, position: f64) {
+    let range = adjustment.upper() - adjustment.page_size() - adjustment.lower();
+    adjustment.set_value(adjustment.lower() + range.max(0.0) * position);
+}
+
+/// The number of children in a row, read from the current layout
+fn flow_box_get_stride(flow_box: &gtk::FlowBox, length: usize) -> usize {
+    if length < 1 { return 0 }
+
+    let y = |index: usize| flow_box.child_at_index(index as i32).unwrap().allocation().y();
+    let first_row = y(0);
+
+    // a single row holds every child
+    (1..length).find(|index| y(*index) != first_row).unwrap_or(length)
+}
+
+/// The first index in `0..length` for which `predicate` is false,
+/// `predicate` has to be true for every index before that one
+fn partition_point(length: usize, predicate: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, length);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if predicate(middle) { low = middle + 1 } else { high = middle }
+    }
+
+    low
+}
+
 // TODO: rewrite this
 fn format_byte_unit(number: u64) -> String {
     let e = 1024 as u64;
@@ -0,0 +1,88 @@
+use serde::Deserialize;
+
+
+/// Zoom factors that zooming in and out steps through
+const ZOOM_STEPS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.33, 0.5, 0.67, 0.75, 1.0,
+    1.25, 1.5, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0, 16.0
+];
+
+const ZOOM_MODES: &[(&str, ZoomMode)] = &[
+    ("manual", ZoomMode::Manual),
+    ("fit", ZoomMode::Fit),
+    ("fit-width", ZoomMode::FitWidth),
+    ("fit-height", ZoomMode::FitHeight),
+    ("fill", ZoomMode::Fill),
+    ("shrink", ZoomMode::ShrinkOnly)
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ZoomMode {
+    /// Keep the zoom level that was set explicitly
+    Manual,
+    /// Scale the image so that it fits inside the window
+    Fit,
+    FitWidth,
+    FitHeight,
+    /// Scale the image so that it covers the whole window
+    Fill,
+    /// Like `Fit`, but never scale the image above its actual size
+    #[default]
+    ShrinkOnly
+}
+
+impl ZoomMode {
+    /// Calculates the zoom factor for an image of `image` size inside a `viewport`
+    pub fn scale(&self, zoom: f64, image: (f64, f64), viewport: (f64, f64)) -> f64 {
+        let (width, height) = (viewport.0 / image.0, viewport.1 / image.1);
+
+        match self {
+            ZoomMode::Manual => zoom,
+            ZoomMode::Fit => width.min(height),
+            ZoomMode::FitWidth => width,
+            ZoomMode::FitHeight => height,
+            ZoomMode::Fill => width.max(height),
+            ZoomMode::ShrinkOnly => width.min(height).min(1.0)
+        }
+    }
+}
+
+impl std::str::FromStr for ZoomMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ZOOM_MODES.iter()
+            .find(|(mode, _)| *mode == name)
+            .map(|(_, mode)| *mode)
+            .ok_or_else(|| format!("unknown zoom mode `{name}`"))
+    }
+}
+
+impl TryFrom<String> for ZoomMode {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+/// Keeps a zoom factor that was set explicitly within what can be shown
+pub fn clamp(zoom: f64) -> f64 {
+    zoom.clamp(0.01, 100.0)
+}
+
+pub fn zoom_in(zoom: f64) -> f64 {
+    ZOOM_STEPS.iter()
+        .find(|step| **step > zoom + f64::EPSILON)
+        .copied()
+        .unwrap_or(*ZOOM_STEPS.last().unwrap())
+}
+
+pub fn zoom_out(zoom: f64) -> f64 {
+    ZOOM_STEPS.iter()
+        .rev()
+        .find(|step| **step < zoom - f64::EPSILON)
+        .copied()
+        .unwrap_or(ZOOM_STEPS[0])
+}